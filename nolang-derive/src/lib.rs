@@ -0,0 +1,247 @@
+//! Derive macros for the conversions between Rust types and nolang values, used by hosts to pass
+//! typed configuration to scripts and read typed results back. They're re-exported by `nolang`
+//! with the `derive` feature
+//!
+//! - structs with named fields are maps, from field names to their values
+//! - tuple structs are lists, unless they have a single field, which is converted by itself
+//! - unit structs are `none`
+//! - unit enum variants are the name of the variant, and the variants with fields are maps with
+//!   the name of the variant as their only key, holding the fields like a struct would
+//!
+//! Missing fields are read as `none`, so `Option` fields can be left out of maps
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as Tokens;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Generics, Ident};
+
+#[proc_macro_derive(IntoPrimitive)]
+pub fn derive_into_primitive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let generics = bounded(input.generics.clone(), quote!(::nolang::IntoPrimitive));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match input.data {
+        Data::Struct(ref data) => {
+            let bindings = bindings(&data.fields);
+            let pattern = pattern(quote!(#name), &data.fields, &bindings);
+            let value = into_value(&data.fields, &bindings);
+            quote! {
+                let #pattern = self;
+                #value
+            }
+        }
+        Data::Enum(ref data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let variant_name = &variant.ident;
+                let tag = variant_name.to_string();
+                let bindings = bindings(&variant.fields);
+                let pattern = pattern(quote!(#name::#variant_name), &variant.fields, &bindings);
+
+                let value = match variant.fields {
+                    Fields::Unit => quote!(::nolang::Primitive::Str(#tag.to_string())),
+                    ref fields => {
+                        let value = into_value(fields, &bindings);
+                        quote! {
+                            let mut tagged = ::std::collections::BTreeMap::new();
+                            tagged.insert(#tag.to_string(), #value);
+                            ::nolang::Primitive::map(tagged)
+                        }
+                    }
+                };
+                quote!(#pattern => { #value })
+            });
+            quote! {
+                match self {
+                    #(#arms)*
+                }
+            }
+        }
+        Data::Union(_) => return unsupported(name),
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::nolang::IntoPrimitive for #name #ty_generics #where_clause {
+            fn into_pri(self) -> ::nolang::Primitive {
+                #body
+            }
+        }
+    };
+    expanded.into()
+}
+
+#[proc_macro_derive(FromPrimitive)]
+pub fn derive_from_primitive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let name = &input.ident;
+    let generics = bounded(input.generics.clone(), quote!(::nolang::FromPrimitive));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let body = match input.data {
+        Data::Struct(ref data) => from_value(quote!(#name), &data.fields, &name.to_string()),
+        Data::Enum(ref data) => {
+            let units = data
+                .variants
+                .iter()
+                .filter(|variant| matches!(variant.fields, Fields::Unit))
+                .map(|variant| {
+                    let variant_name = &variant.ident;
+                    let tag = variant_name.to_string();
+                    quote!(#tag => Ok(#name::#variant_name),)
+                });
+
+            let tagged = data
+                .variants
+                .iter()
+                .filter(|variant| !matches!(variant.fields, Fields::Unit))
+                .map(|variant| {
+                    let variant_name = &variant.ident;
+                    let tag = variant_name.to_string();
+                    let value = from_value(
+                        quote!(#name::#variant_name),
+                        &variant.fields,
+                        &format!("{}::{}", name, tag),
+                    );
+                    quote!(#tag => { #value })
+                });
+
+            let expected = name.to_string();
+            quote! {
+                let unknown = |tag: &str| ::nolang::error!(Type; "`{}` has no variant named `{}`", #expected, tag);
+                match value {
+                    ::nolang::Primitive::Str(tag) => match tag.as_str() {
+                        #(#units)*
+                        _ => Err(unknown(&tag)),
+                    },
+                    ::nolang::Primitive::Map(map) if map.borrow().len() == 1 => {
+                        let (tag, value) = map.borrow().iter().next().map(|(k, v)| (k.clone(), v.clone())).unwrap();
+                        match tag.as_str() {
+                            #(#tagged)*
+                            _ => Err(unknown(&tag)),
+                        }
+                    }
+                    e => Err(::nolang::error!(Type; "expected a variant of `{}`, found `{}`", #expected, e.type_name())),
+                }
+            }
+        }
+        Data::Union(_) => return unsupported(name),
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::nolang::FromPrimitive for #name #ty_generics #where_clause {
+            fn from_pri(value: ::nolang::Primitive) -> ::std::result::Result<Self, ::nolang::NolangError> {
+                #body
+            }
+        }
+    };
+    expanded.into()
+}
+
+/// Require every type parameter to implement the derived trait
+fn bounded(mut generics: Generics, bound: Tokens) -> Generics {
+    let params: Vec<Ident> = generics.type_params().map(|param| param.ident.clone()).collect();
+    let where_clause = generics.make_where_clause();
+    for param in params {
+        where_clause.predicates.push(parse_quote!(#param: #bound));
+    }
+    generics
+}
+
+/// The names the fields are bound to when taking the value apart
+fn bindings(fields: &Fields) -> Vec<Ident> {
+    (0..fields.len()).map(|i| format_ident!("field_{}", i)).collect()
+}
+
+/// Pattern binding every field of a struct or variant
+fn pattern(path: Tokens, fields: &Fields, bindings: &[Ident]) -> Tokens {
+    match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote!(#path { #(#names: #bindings),* })
+        }
+        Fields::Unnamed(_) => quote!(#path(#(#bindings),*)),
+        Fields::Unit => quote!(#path),
+    }
+}
+
+/// The nolang value of the fields that were bound
+fn into_value(fields: &Fields, bindings: &[Ident]) -> Tokens {
+    match fields {
+        Fields::Named(named) => {
+            let keys = named
+                .named
+                .iter()
+                .map(|field| field.ident.as_ref().unwrap().to_string());
+            quote! {{
+                let mut entries = ::std::collections::BTreeMap::new();
+                #(entries.insert(#keys.to_string(), ::nolang::IntoPrimitive::into_pri(#bindings));)*
+                ::nolang::Primitive::map(entries)
+            }}
+        }
+        Fields::Unnamed(_) if bindings.len() == 1 => {
+            let binding = &bindings[0];
+            quote!(::nolang::IntoPrimitive::into_pri(#binding))
+        }
+        Fields::Unnamed(_) => {
+            quote!(::nolang::Primitive::list(
+                vec![#(::nolang::IntoPrimitive::into_pri(#bindings)),*]
+            ))
+        }
+        Fields::Unit => quote!(::nolang::Primitive::None),
+    }
+}
+
+/// Build the struct or variant at `path` from `value`, `described` names it in the errors
+fn from_value(path: Tokens, fields: &Fields, described: &str) -> Tokens {
+    match fields {
+        Fields::Named(named) => {
+            let fields = named.named.iter().map(|field| {
+                let name = field.ident.as_ref().unwrap();
+                let key = name.to_string();
+                let note = format!("in field `{}` of `{}`", key, described);
+                quote! {
+                    #name: ::nolang::FromPrimitive::from_pri(
+                        map.get(#key).cloned().unwrap_or(::nolang::Primitive::None),
+                    )
+                    .map_err(|e| e.with_note(#note, None))?
+                }
+            });
+            quote! {
+                let map = match value {
+                    ::nolang::Primitive::Map(map) => map,
+                    e => return Err(::nolang::error!(Type; "expected a map for `{}`, found `{}`", #described, e.type_name())),
+                };
+                let map = map.borrow();
+                Ok(#path { #(#fields),* })
+            }
+        }
+        Fields::Unnamed(_) if fields.len() == 1 => quote!(Ok(#path(::nolang::FromPrimitive::from_pri(value)?))),
+        Fields::Unnamed(_) => {
+            let count = fields.len();
+            let items = (0..count).map(|i| {
+                let note = format!("in item {} of `{}`", i, described);
+                quote!(::nolang::FromPrimitive::from_pri(items.next().unwrap()).map_err(|e| e.with_note(#note, None))?)
+            });
+            quote! {
+                let items = match value {
+                    ::nolang::Primitive::List(list) if list.borrow().len() == #count => list.borrow().clone(),
+                    e => return Err(::nolang::error!(Type; "expected a list of {} items for `{}`, found {}", #count, #described, e)),
+                };
+                let mut items = items.into_iter();
+                Ok(#path(#(#items),*))
+            }
+        }
+        Fields::Unit => quote! {
+            match value {
+                ::nolang::Primitive::None => Ok(#path),
+                e => Err(::nolang::error!(Type; "expected `none` for `{}`, found `{}`", #described, e.type_name())),
+            }
+        },
+    }
+}
+
+fn unsupported(name: &Ident) -> TokenStream {
+    let error = syn::Error::new(name.span(), "unions can't be converted to nolang values");
+    error.to_compile_error().into()
+}
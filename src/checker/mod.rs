@@ -0,0 +1,439 @@
+use crate::error::NolangError;
+use crate::frontend::{
+    Literal, Op, OpKind, Param, Span, Statement, StatementKind, Tokens as Tok, Type,
+};
+use crate::interpreter::builtins;
+use crate::modules::module_name;
+use std::{collections::HashMap, path::Path};
+
+/// Infers the type of every expression and reports the operations that would fail at runtime
+/// before the program is run. Values whose type can't be known statically are `Any`
+pub struct Checker {
+    scopes: Vec<HashMap<String, Binding>>,
+    errors: Vec<NolangError>,
+    returns: Vec<(Type, Type)>, // declared and returned types of the functions being checked
+}
+
+/// The type of a variable, annotated types must be kept by the assignments to it
+struct Binding {
+    ty: Type,
+    annotated: bool,
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        // native functions take any value, so their calls aren't checked
+        let globals = builtins()
+            .into_keys()
+            .map(|name| {
+                let binding = Binding {
+                    ty: Type::Any,
+                    annotated: false,
+                };
+                (name, binding)
+            })
+            .collect();
+
+        Self {
+            scopes: vec![globals],
+            errors: Vec::new(),
+            returns: Vec::new(),
+        }
+    }
+}
+
+/// Check a whole program, returning every type error found in it
+pub fn check(statements: &[Statement]) -> Vec<NolangError> {
+    Checker::default().check(statements)
+}
+
+impl Checker {
+    /// Check the statements, keeping their bindings for the next call, like the repl needs
+    pub fn check(&mut self, statements: &[Statement]) -> Vec<NolangError> {
+        for statement in statements {
+            self.statement(statement);
+        }
+        std::mem::take(&mut self.errors)
+    }
+
+    fn error(&mut self, error: NolangError) -> Type {
+        self.errors.push(error);
+        Type::Any
+    }
+
+    /// Bind a global defined outside of nolang code, whose type isn't known
+    pub fn declare(&mut self, name: &str) {
+        self.scopes[0].insert(
+            name.to_string(),
+            Binding {
+                ty: Type::Any,
+                annotated: false,
+            },
+        );
+    }
+
+    fn define(&mut self, name: &str, ty: Type) {
+        self.bind(name, ty, false)
+    }
+
+    fn bind(&mut self, name: &str, ty: Type, annotated: bool) {
+        let binding = Binding { ty, annotated };
+        self.scopes.last_mut().unwrap().insert(name.to_string(), binding);
+    }
+
+    fn lookup(&self, name: &str) -> Type {
+        // names that aren't defined yet may be defined before the code using them runs
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).map(|binding| binding.ty.clone()))
+            .unwrap_or(Type::Any)
+    }
+
+    /// Report an error if a value of type `found` can't be used where `expected` is needed
+    fn expect(&mut self, expected: &Type, found: &Type, what: &str, span: &Span) {
+        if !expected.accepts(found) {
+            self.error(crate::error!(Type, span.clone(); "mismatched types in {}: expected `{}`, found `{}`", what, expected, found));
+        }
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Type {
+        match statement.kind {
+            StatementKind::Op(ref op) => return self.op(op),
+            StatementKind::Assign(ref name, ref annotation, ref value) => {
+                let found = self.op(value);
+                match annotation {
+                    Some(expected) => {
+                        self.expect(
+                            expected,
+                            &found,
+                            &format!("the assignment of `{}`", name),
+                            &value.span,
+                        );
+                        self.bind(name, expected.clone(), true);
+                    }
+                    // `none` is usually a placeholder for a value assigned later
+                    None if found == Type::None => self.define(name, Type::Any),
+                    None => self.define(name, found),
+                }
+            }
+            StatementKind::Reassign(ref name, ref value) => {
+                let found = self.op(value);
+                let binding = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name));
+                match binding {
+                    Some(binding) if binding.annotated => {
+                        let expected = binding.ty.clone();
+                        self.expect(
+                            &expected,
+                            &found,
+                            &format!("the assignment of `{}`", name),
+                            &value.span,
+                        );
+                    }
+                    // without an annotation the variable can hold the types of all its values
+                    Some(binding) => binding.ty = binding.ty.join(&found),
+                    None => (),
+                }
+            }
+            StatementKind::FuncAssign(ref name, ref params, ref annotation, ref body) => {
+                self.function(Some(name), params, annotation, body);
+            }
+            // modules are checked when they're imported, so their values can have any type here
+            StatementKind::Import(ref path, ref names) => match names.is_empty() {
+                true => self.define(&module_name(Path::new(path)), Type::Any),
+                false => names.iter().for_each(|name| self.define(name, Type::Any)),
+            },
+            StatementKind::Export(ref statement) => return self.statement(statement),
+        }
+        Type::None
+    }
+
+    /// Check the body of a function and get the function's type. Named functions are bound in the
+    /// current scope
+    fn function(
+        &mut self,
+        name: Option<&str>,
+        params: &[Param],
+        annotation: &Option<Type>,
+        body: &Statement,
+    ) -> Type {
+        let param_types: Vec<Type> = params
+            .iter()
+            .map(|param| param.annotation.clone().unwrap_or(Type::Any))
+            .collect();
+        let declared = annotation.clone().unwrap_or(Type::Any);
+
+        // bind the function before checking the body, so recursive calls are checked too
+        if let Some(name) = name {
+            self.define(
+                name,
+                Type::Function(param_types.clone(), Box::new(declared.clone())),
+            );
+        }
+
+        self.scopes.push(HashMap::new());
+        for (param, ty) in params.iter().zip(&param_types) {
+            self.bind(&param.name, ty.clone(), param.annotation.is_some());
+        }
+
+        // each `return` checks its own value, so the body only checks the value it ends with
+        self.returns.push((declared.clone(), Type::Never));
+        let found = self.statement(body);
+        let (_, returned) = self.returns.pop().unwrap();
+
+        self.scopes.pop();
+
+        let what = match name {
+            Some(name) => format!("the return value of `{}`", name),
+            None => "the return value of the lambda".to_string(),
+        };
+        self.expect(&declared, &found, &what, &body.span);
+
+        let ret = match annotation {
+            Some(_) => declared,
+            None => found.join(&returned),
+        };
+        let ty = Type::Function(param_types, Box::new(ret));
+        if let Some(name) = name {
+            self.define(name, ty.clone());
+        }
+        ty
+    }
+
+    fn literal(&mut self, literal: &Literal) -> Type {
+        match literal {
+            Literal::Bool(_) => Type::Bool,
+            Literal::None => Type::None,
+            Literal::String(_) => Type::Str,
+            Literal::Num(_) => Type::Num,
+            Literal::Int(_) => Type::Int,
+            Literal::BigInt(_) => Type::BigInt,
+            Literal::Decimal(_) => Type::Decimal,
+            Literal::Operation(op) => self.op(op),
+            Literal::VarNormal(name) => self.lookup(name),
+        }
+    }
+
+    fn op(&mut self, op: &Op) -> Type {
+        let span = &op.span;
+        match op.kind {
+            OpKind::Primary(ref literal) => self.literal(literal),
+
+            OpKind::Unary(ref operator, ref right) => {
+                let right = self.literal(right);
+                match operator {
+                    Tok::Minus if !right.is_number() => self.error(
+                        crate::error!(Type, span.clone(); "can't use `-` operator with {}", right),
+                    ),
+                    Tok::Minus if matches!(right, Type::Int | Type::BigInt | Type::Decimal) => right,
+                    Tok::Minus => Type::Num,
+                    _ => Type::Bool,
+                }
+            }
+
+            OpKind::Binary(ref left, Tok::And | Tok::Or, ref right) => {
+                let left = self.op(left);
+                let right = self.op(right);
+                left.join(&right)
+            }
+
+            OpKind::Binary(ref left, ref operator, ref right) => {
+                let left = self.op(left);
+                let right = self.op(right);
+                self.binary(&left, operator, &right, span)
+            }
+
+            OpKind::Grouping(ref op) => self.op(op),
+
+            OpKind::List(ref items) => {
+                items.iter().for_each(|item| drop(self.op(item)));
+                Type::List
+            }
+
+            OpKind::Map(ref entries) => {
+                entries.iter().for_each(|(_, value)| drop(self.op(value)));
+                Type::Map
+            }
+
+            // every value can be shown in a string
+            OpKind::Template(ref parts) => {
+                parts.iter().for_each(|part| drop(self.op(part)));
+                Type::Str
+            }
+
+            OpKind::Index(ref value, ref index) => {
+                let (value, index) = (self.op(value), self.op(index));
+                match value {
+                    Type::List => self.expect(&Type::Num, &index, "the index", span),
+                    Type::Map => self.expect(&Type::Str, &index, "the key", span),
+                    // indexing a string gets one of its characters
+                    Type::Str => {
+                        self.expect(&Type::Num, &index, "the index", span);
+                        return Type::Str;
+                    }
+                    Type::Any => (),
+                    e => return self.error(crate::error!(Type, span.clone(); "can't index a value of type `{}`", e)),
+                }
+                Type::Any
+            }
+
+            OpKind::Slice(ref value, ref start, ref end) => {
+                let value = self.op(value);
+                for bound in start.iter().chain(end) {
+                    let ty = self.op(bound);
+                    self.expect(&Type::Num, &ty, "the slice bound", &bound.span);
+                }
+                match value {
+                    Type::List | Type::Str => value,
+                    Type::Any => Type::Any,
+                    e => self.error(crate::error!(Type, span.clone(); "can't slice a value of type `{}`", e)),
+                }
+            }
+
+            OpKind::Lambda(ref params, ref annotation, ref body) => {
+                self.function(None, params, annotation, body)
+            }
+
+            OpKind::Access(ref module, ref name) => match self.op(module) {
+                Type::Any | Type::Map => Type::Any,
+                e => self.error(crate::error!(Type, span.clone(); "can't access `{}` on a value of type `{}`", name, e)),
+            },
+
+            OpKind::Call(ref called, ref arguments) => {
+                let called = self.op(called);
+                let arguments: Vec<(Type, &Span)> =
+                    arguments.iter().map(|a| (self.op(a), &a.span)).collect();
+
+                match called {
+                    Type::Function(params, ret) => {
+                        if arguments.len() < params.len() {
+                            return self.error(crate::error!(Call, span.clone(); "Missing arguments for function call: expected {}, found {}", params.len(), arguments.len()));
+                        }
+                        for (expected, (found, span)) in params.iter().zip(arguments) {
+                            self.expect(expected, &found, "the function argument", span);
+                        }
+                        *ret
+                    }
+                    Type::Any => Type::Any,
+                    e => self.error(
+                        crate::error!(Call, span.clone(); "can't call a value of type `{}`", e),
+                    ),
+                }
+            }
+
+            OpKind::Block(ref statements) => {
+                self.scopes.push(HashMap::new());
+                let mut ty = Type::None;
+                for statement in statements {
+                    ty = self.statement(statement);
+                }
+                self.scopes.pop();
+                ty
+            }
+
+            OpKind::If(ref condition, ref block, ref else_block) => {
+                self.op(condition);
+                let then = self.op(block);
+                let otherwise = self.op(else_block);
+                then.join(&otherwise)
+            }
+
+            OpKind::While(ref condition, ref body) => {
+                self.op(condition);
+                self.op(body);
+                Type::None
+            }
+
+            OpKind::For(ref name, ref iterable, ref body) => {
+                let item = match self.op(iterable) {
+                    Type::Str | Type::Map => Type::Str, // characters and keys
+                    Type::List | Type::Any => Type::Any,
+                    e => self.error(crate::error!(Type, iterable.span.clone(); "can't iterate over a value of type `{}`", e)),
+                };
+                self.scopes.push(HashMap::new());
+                self.define(name, item);
+                self.op(body);
+                self.scopes.pop();
+                Type::None
+            }
+
+            // they jump out of the expression, so it never has a value
+            OpKind::Break | OpKind::Continue => Type::Never,
+
+            OpKind::Return(ref value) => {
+                let found = match value {
+                    Some(value) => self.op(value),
+                    None => Type::None,
+                };
+                let (expected, returned) = self.returns.last_mut().unwrap();
+                *returned = returned.join(&found);
+
+                let expected = expected.clone();
+                self.expect(&expected, &found, "the returned value", span);
+                Type::Never
+            }
+
+            OpKind::Error => Type::Any,
+        }
+    }
+
+    /// The type of a binary operation, following the rules of `Primitive`'s operators
+    fn binary(&mut self, left: &Type, operator: &Tok, right: &Type, span: &Span) -> Type {
+        let both = |ty: &Type| ty.accepts(left) && ty.accepts(right);
+        let numbers = left.is_number() && right.is_number();
+
+        let result = match operator {
+            Tok::Plus if *left == Type::Any && *right == Type::Any => Some(Type::Any),
+            Tok::Plus | Tok::Minus | Tok::Asterisk | Tok::Slash | Tok::SlashSlash | Tok::Percent | Tok::Pow
+                if numbers =>
+            {
+                arithmetic(left, operator, right)
+            }
+            Tok::Plus => [Type::Str, Type::List].iter().find(|ty| both(ty)).cloned(),
+            Tok::Minus | Tok::Asterisk | Tok::Slash | Tok::SlashSlash | Tok::Percent | Tok::Pow => None,
+            // numbers that can't be used together can't be compared either
+            _ if numbers => arithmetic(left, &Tok::Minus, right).map(|_| Type::Bool),
+            Tok::Comp | Tok::Different => [Type::Str, Type::Bool, Type::None, Type::List, Type::Map]
+                .iter()
+                .find(|ty| both(ty))
+                .map(|_| Type::Bool),
+            _ => [Type::Str, Type::Bool]
+                .iter()
+                .find(|ty| both(ty))
+                .map(|_| Type::Bool),
+        };
+
+        match result {
+            Some(ty) => ty,
+            None => self.error(
+                crate::error!(Type, span.clone(); "can't use `{}` operator between {} and {}", operator, left, right),
+            ),
+        }
+    }
+}
+
+/// The type of an arithmetic operator between numbers, following the promotions of `Primitive`:
+/// integers, then big integers, then decimals. Floats mix with integers but not with decimals
+fn arithmetic(left: &Type, operator: &Tok, right: &Type) -> Option<Type> {
+    let either = |ty: &Type| left == ty || right == ty;
+    if either(&Type::Any) {
+        return Some(Type::Any);
+    }
+    if either(&Type::Num) {
+        return Some(Type::Num).filter(|_| !either(&Type::Decimal));
+    }
+
+    let widest = [Type::Decimal, Type::BigInt]
+        .iter()
+        .find(|ty| either(ty))
+        .cloned()
+        .unwrap_or(Type::Int);
+    match operator {
+        // integers are divided as floats. Their powers are integers, but floats for negative
+        // exponents, so they can be either
+        Tok::Slash if widest == Type::Int => Some(Type::Num),
+        Tok::Pow if widest == Type::Int => Some(Type::Any),
+        Tok::Slash => Some(Type::Decimal),
+        _ => Some(widest),
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{frontend::Span, interpreter::Primitive};
+use std::{fmt, rc::Rc};
+
+/// Instructions understood by the vm. Slots are relative to the start of the current call frame,
+/// where slot 0 holds the called function
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Constant(usize), // push a value from the constant pool
+    Pop,
+    Drop(usize), // pop that many values, used to leave a loop from the middle of its body
+
+    GetLocal(usize),
+    SetLocal(usize),    // overwrite a local with the value on top, without popping it
+    GetUpvalue(usize),  // get a variable captured by the running closure
+    SetUpvalue(usize),  // overwrite a captured variable with the value on top, without popping it
+    GetGlobal(usize),   // the constant holds the name of the variable
+    SetGlobal(usize),   // pop the value on top into a global
+    AssignGlobal(usize), // overwrite a global that must exist with the value on top, without popping it
+    Import(usize),      // push the module with the constant path, running it if needed
+    GetField(usize),    // replace the module or map on top with its field with the constant name
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    FloorDiv,
+    Rem,
+    Pow,
+    Neg,
+    Not,
+
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+
+    Jump(usize),        // absolute target
+    JumpIfFalse(usize), // doesn't pop the condition
+    JumpIfTrue(usize),
+    Iter,           // replace the value on top with the list of values a `for` loop goes through
+    ForNext(usize), // push the next item of the list under the position on top, or jump when done
+
+    List(usize),      // make a list of that many values
+    Map(usize),       // make a map of that many pairs of keys and values
+    Template(usize),  // join that many values into a string, the parts of a string with `${...}`
+    Index,
+    Slice,            // the value, the start and the end are on the stack, missing bounds are none
+    Closure(usize),  // make a closure of a function of the chunk, capturing its upvalues
+    Call(usize),     // number of arguments
+    TailCall(usize), // a call whose value is returned right after, nolang functions reuse the frame
+    EndScope(usize), // drop that many locals that are under the value on top
+    Return,
+}
+
+/// Compiled code, with the constants and functions it uses and the span of every instruction
+#[derive(Debug, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub spans: Vec<Span>,
+    pub constants: Vec<Primitive>,
+    pub functions: Vec<Rc<Function>>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.code.push(instruction);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, value: Primitive) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    pub fn add_function(&mut self, function: Rc<Function>) -> usize {
+        self.functions.push(function);
+        self.functions.len() - 1
+    }
+}
+
+/// Where a closure takes one of its upvalues from when it's made: a local of the function that
+/// makes it, or one of that function's own upvalues
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capture {
+    pub is_local: bool,
+    pub index: usize,
+}
+
+/// A function compiled to bytecode, the whole program is compiled to a function as well
+pub struct Function {
+    pub name: String,
+    pub params: Vec<Rc<str>>,
+    pub upvalues: Vec<Capture>,
+    pub chunk: Chunk,
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<function {}>", self.name)
+    }
+}
+
+impl fmt::Display for Function {
+    /// Disassemble the function, and the functions in its constant pool
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "== {} ==", self.name)?;
+        for (index, instruction) in self.chunk.code.iter().enumerate() {
+            let span = &self.chunk.spans[index];
+            write!(f, "{:04} {:>4}:{:<3} {:?}", index, span.line, span.column, instruction)?;
+            if let Instruction::Constant(c)
+            | Instruction::GetGlobal(c)
+            | Instruction::SetGlobal(c)
+            | Instruction::AssignGlobal(c)
+            | Instruction::Import(c)
+            | Instruction::GetField(c) = instruction
+            {
+                write!(f, " ({})", self.chunk.constants[*c])?;
+            }
+            if let Instruction::Closure(c) = instruction {
+                write!(f, " ({:?})", self.chunk.functions[*c])?;
+            }
+            writeln!(f)?;
+        }
+
+        for function in &self.chunk.functions {
+            write!(f, "\n{}", function)?;
+        }
+        Ok(())
+    }
+}
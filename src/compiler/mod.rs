@@ -0,0 +1,620 @@
+mod chunk;
+
+pub use chunk::{Capture, Chunk, Function, Instruction};
+
+use crate::error::Result;
+use crate::frontend::{Literal, Op, OpKind, Span, Statement, StatementKind, Tokens as Tok};
+use crate::interpreter::Primitive;
+use crate::modules::module_name;
+use std::{path::Path, rc::Rc};
+
+struct Local {
+    name: String,
+    depth: usize,
+    slot: usize,
+}
+
+/// State of the function being compiled
+struct FunctionState {
+    function: Function,
+    locals: Vec<Local>,
+    depth: usize,
+    height: usize, // how many values the function has on the stack, counting slot 0
+    is_script: bool,
+    loops: Vec<Loop>,
+}
+
+/// A loop being compiled, which `break` and `continue` jump out of
+struct Loop {
+    start: usize,       // where `continue` jumps to
+    height: usize,      // stack height at the start of each iteration
+    breaks: Vec<usize>, // jumps to patch to the end of the loop
+}
+
+impl FunctionState {
+    fn new(name: &str, params: &[String], is_script: bool) -> Self {
+        let locals = params
+            .iter()
+            .enumerate()
+            .map(|(index, param)| Local {
+                name: param.to_string(),
+                depth: 0,
+                slot: index + 1,
+            })
+            .collect();
+
+        Self {
+            function: Function {
+                name: name.to_string(),
+                params: params.iter().map(|p| Rc::from(p.as_str())).collect(),
+                upvalues: Vec::new(),
+                chunk: Chunk::default(),
+            },
+            locals,
+            depth: 0,
+            height: params.len() + 1,
+            is_script,
+            loops: Vec::new(),
+        }
+    }
+}
+
+/// Lowers the AST to bytecode. Every statement and operation leaves exactly one value on the stack
+pub struct Compiler {
+    states: Vec<FunctionState>,
+}
+
+/// Compile the program to a function that can be run by the vm
+pub fn compile(statements: impl Iterator<Item = Statement>) -> Result<Rc<Function>> {
+    let mut compiler = Compiler {
+        states: vec![FunctionState::new("<script>", &[], true)],
+    };
+
+    let statements: Vec<Statement> = statements.collect();
+    let span = match (statements.first(), statements.last()) {
+        (Some(first), Some(last)) => first.span.to(&last.span),
+        _ => Span::default(),
+    };
+    compiler.sequence(&statements, &span)?;
+    compiler.emit(Instruction::Return, &span);
+
+    Ok(Rc::new(compiler.states.pop().unwrap().function))
+}
+
+impl Compiler {
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().unwrap()
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: &Span) -> usize {
+        use Instruction::*;
+
+        let effect: isize = match instruction {
+            Constant(_) | GetLocal(_) | GetUpvalue(_) | GetGlobal(_) | Import(_) | Closure(_) => 1,
+            Pop | SetGlobal(_) | Return => -1,
+            Drop(values) => -(values as isize),
+            ForNext(_) => 1,
+            Add | Sub | Mul | Div | FloorDiv | Rem | Pow | Index => -1,
+            Slice => -2,
+            List(items) | Template(items) => 1 - items as isize,
+            Map(entries) => 1 - 2 * entries as isize,
+            Equal | NotEqual | Greater | GreaterEqual | Less | LessEqual => -1,
+            Call(arguments) | TailCall(arguments) => -(arguments as isize),
+            EndScope(locals) => -(locals as isize),
+            SetLocal(_) | SetUpvalue(_) | AssignGlobal(_) | GetField(_) | Neg | Not => 0,
+            Jump(_) | JumpIfFalse(_) | JumpIfTrue(_) | Iter => 0,
+        };
+
+        let state = self.state();
+        state.height = (state.height as isize + effect) as usize;
+        state.function.chunk.write(instruction, span.clone())
+    }
+
+    fn constant(&mut self, value: Primitive, span: &Span) {
+        let index = self.state().function.chunk.add_constant(value);
+        self.emit(Instruction::Constant(index), span);
+    }
+
+    fn name(&mut self, name: &str) -> usize {
+        self.state().function.chunk.add_constant(Primitive::Str(name.to_string()))
+    }
+
+    /// Point the jump at `index` to the next instruction
+    fn patch(&mut self, index: usize) {
+        let chunk = &mut self.state().function.chunk;
+        let target = chunk.code.len();
+        chunk.code[index] = match chunk.code[index] {
+            Instruction::Jump(_) => Instruction::Jump(target),
+            Instruction::JumpIfFalse(_) => Instruction::JumpIfFalse(target),
+            Instruction::JumpIfTrue(_) => Instruction::JumpIfTrue(target),
+            Instruction::ForNext(_) => Instruction::ForNext(target),
+            _ => unreachable!(),
+        }
+    }
+
+    fn begin_scope(&mut self) {
+        self.state().depth += 1;
+    }
+
+    /// Drop the locals of the scope, keeping the value on top of them
+    fn end_scope(&mut self, span: &Span) {
+        let state = self.state();
+        state.depth -= 1;
+
+        let depth = state.depth;
+        let count = state.locals.iter().filter(|l| l.depth > depth).count();
+        state.locals.retain(|l| l.depth <= depth);
+
+        if count > 0 {
+            self.emit(Instruction::EndScope(count), span);
+        }
+    }
+
+    /// Bind the value on top of the stack to `name`, globals are popped and locals stay in place
+    fn define(&mut self, name: &str, span: &Span) {
+        let state = self.state();
+        let depth = state.depth;
+
+        if state.is_script && depth == 0 {
+            let name = self.name(name);
+            self.emit(Instruction::SetGlobal(name), span);
+        } else if let Some(slot) = state
+            .locals
+            .iter()
+            .rev()
+            .find(|l| l.name == name && l.depth == depth)
+            .map(|l| l.slot)
+        {
+            self.emit(Instruction::SetLocal(slot), span);
+            self.emit(Instruction::Pop, span);
+        } else {
+            let slot = state.height - 1;
+            state.locals.push(Local {
+                name: name.to_string(),
+                depth,
+                slot,
+            });
+        }
+    }
+
+    fn is_global_scope(&mut self) -> bool {
+        let state = self.state();
+        state.is_script && state.depth == 0
+    }
+
+    /// Start a loop at the next instruction, with the stack as it is at the start of each iteration
+    fn begin_loop(&mut self) -> usize {
+        let state = self.state();
+        let start = state.function.chunk.code.len();
+        state.loops.push(Loop {
+            start,
+            height: state.height,
+            breaks: Vec::new(),
+        });
+        start
+    }
+
+    /// Point the `break`s of the innermost loop to the next instruction
+    fn end_loop(&mut self) {
+        let breaks = self.state().loops.pop().unwrap().breaks;
+        for jump in breaks {
+            self.patch(jump);
+        }
+    }
+
+    /// `break` and `continue` drop every value the loop body pushed before jumping
+    fn loop_jump(&mut self, is_break: bool, span: &Span) {
+        let state = self.state();
+        let height = state.height;
+        let (start, values) = match state.loops.last() {
+            Some(current) => (current.start, height - current.height),
+            None => unreachable!(), // the parser only allows them inside of loops
+        };
+
+        if values > 0 {
+            self.emit(Instruction::Drop(values), span);
+        }
+        let jump = self.emit(Instruction::Jump(start), span);
+        if is_break {
+            self.state().loops.last_mut().unwrap().breaks.push(jump);
+        }
+        // the code after the jump is never run, but it's compiled as if the jump left a value
+        self.state().height = height + 1;
+    }
+
+    /// Compile the statements, leaving the value of the last one (or none) on the stack
+    fn sequence(&mut self, statements: &[Statement], span: &Span) -> Result<()> {
+        if statements.is_empty() {
+            self.constant(Primitive::None, span);
+        }
+
+        for (index, statement) in statements.iter().enumerate() {
+            self.statement(statement)?;
+            if index + 1 < statements.len() {
+                self.emit(Instruction::Pop, &statement.span);
+            }
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, statement: &Statement) -> Result<()> {
+        let span = &statement.span;
+        match statement.kind {
+            StatementKind::Op(ref op) => return self.op(op),
+            StatementKind::Assign(ref name, _, ref value) => {
+                self.op(value)?;
+                self.define(name, span);
+            }
+            StatementKind::Reassign(ref name, ref value) => {
+                self.op(value)?;
+                let current = self.states.len() - 1;
+                let instruction = match self.resolve_local(current, name) {
+                    Some(slot) => Instruction::SetLocal(slot),
+                    None => match self.resolve_upvalue(current, name) {
+                        Some(index) => Instruction::SetUpvalue(index),
+                        None => Instruction::AssignGlobal(self.name(name)),
+                    },
+                };
+                self.emit(instruction, span);
+                self.emit(Instruction::Pop, span);
+            }
+            StatementKind::FuncAssign(ref name, ref params, _, ref body) => {
+                let params: Vec<String> = params.iter().map(|param| param.name.to_string()).collect();
+
+                // locals are declared before the body is compiled, so the function can capture itself
+                let is_global = self.is_global_scope();
+                if !is_global {
+                    self.constant(Primitive::None, span);
+                    self.define(name, span);
+                }
+
+                self.closure(name, &params, body, span)?;
+
+                match is_global {
+                    true => self.define(name, span),
+                    false => {
+                        let slot = self.resolve_local(self.states.len() - 1, name).unwrap();
+                        self.emit(Instruction::SetLocal(slot), span);
+                        self.emit(Instruction::Pop, span);
+                    }
+                }
+            }
+            StatementKind::Import(ref path, ref names) => {
+                let path_name = self.name(path);
+                if names.is_empty() {
+                    self.emit(Instruction::Import(path_name), span);
+                    self.define(&module_name(Path::new(path)), span);
+                }
+                // modules are cached, so importing again for each name is cheap
+                for name in names {
+                    self.emit(Instruction::Import(path_name), span);
+                    let field = self.name(name);
+                    self.emit(Instruction::GetField(field), span);
+                    self.define(name, span);
+                }
+            }
+            StatementKind::Export(ref statement) => return self.statement(statement),
+        }
+        self.constant(Primitive::None, span);
+        Ok(())
+    }
+
+    /// Compile the function and make a closure of it, leaving it on the stack
+    fn closure(&mut self, name: &str, params: &[String], body: &Statement, span: &Span) -> Result<()> {
+        let function = self.function(name, params, body)?;
+        let index = self.state().function.chunk.add_function(function);
+        self.emit(Instruction::Closure(index), span);
+        Ok(())
+    }
+
+    fn function(&mut self, name: &str, params: &[String], body: &Statement) -> Result<Rc<Function>> {
+        self.states.push(FunctionState::new(name, params, false));
+
+        // the body gets its own scope, like the environment the tree-walker creates for it
+        self.begin_scope();
+        let result = match body.kind {
+            StatementKind::Op(ref op) => self.tail_op(op),
+            _ => self.statement(body),
+        };
+        self.emit(Instruction::Return, &body.span);
+
+        let state = self.states.pop().unwrap();
+        result.map(|_| Rc::new(state.function))
+    }
+
+    fn literal(&mut self, literal: &Literal, span: &Span) -> Result<()> {
+        match literal {
+            Literal::Bool(b) => self.constant(Primitive::Bool(*b), span),
+            Literal::None => self.constant(Primitive::None, span),
+            Literal::String(s) => self.constant(Primitive::Str(s.to_string()), span),
+            Literal::Num(n) => self.constant(Primitive::Num(*n), span),
+            Literal::Int(n) => self.constant(Primitive::Int(*n), span),
+            Literal::BigInt(n) => self.constant(Primitive::big_int(n.clone()), span),
+            Literal::Decimal(n) => self.constant(Primitive::decimal(n.clone()), span),
+            Literal::Operation(op) => return self.op(op),
+            Literal::VarNormal(name) => self.variable(name, span),
+        }
+        Ok(())
+    }
+
+    fn variable(&mut self, name: &str, span: &Span) {
+        let current = self.states.len() - 1;
+        let instruction = match self.resolve_local(current, name) {
+            Some(slot) => Instruction::GetLocal(slot),
+            None => match self.resolve_upvalue(current, name) {
+                Some(index) => Instruction::GetUpvalue(index),
+                None => Instruction::GetGlobal(self.name(name)),
+            },
+        };
+        self.emit(instruction, span);
+    }
+
+    /// Slot of the innermost local called `name` in the function at `state`
+    fn resolve_local(&self, state: usize, name: &str) -> Option<usize> {
+        let locals = &self.states[state].locals;
+        locals.iter().rev().find(|l| l.name == name).map(|l| l.slot)
+    }
+
+    /// Index of the upvalue of the function at `state` that captures the variable `name` of one of
+    /// the enclosing functions, adding it if it's the first time the function uses it
+    fn resolve_upvalue(&mut self, state: usize, name: &str) -> Option<usize> {
+        if state == 0 {
+            return None;
+        }
+
+        let capture = match self.resolve_local(state - 1, name) {
+            Some(slot) => Capture {
+                is_local: true,
+                index: slot,
+            },
+            None => Capture {
+                is_local: false,
+                index: self.resolve_upvalue(state - 1, name)?,
+            },
+        };
+
+        let upvalues = &mut self.states[state].function.upvalues;
+        match upvalues.iter().position(|u| *u == capture) {
+            Some(index) => Some(index),
+            None => {
+                upvalues.push(capture);
+                Some(upvalues.len() - 1)
+            }
+        }
+    }
+
+    /// Compile an operation whose value is returned by the function being compiled, so the calls
+    /// it ends with don't need a new frame
+    fn tail_op(&mut self, op: &Op) -> Result<()> {
+        let span = &op.span;
+        match op.kind {
+            OpKind::Call(ref called, ref arguments) => {
+                self.op(called)?;
+                for argument in arguments {
+                    self.op(argument)?;
+                }
+                self.emit(Instruction::TailCall(arguments.len()), span);
+            }
+
+            OpKind::If(ref condition, ref block, ref else_block) => {
+                self.op(condition)?;
+                let else_jump = self.emit(Instruction::JumpIfFalse(0), span);
+                self.emit(Instruction::Pop, span);
+                self.tail_op(block)?;
+                let end_jump = self.emit(Instruction::Jump(0), span);
+
+                self.patch(else_jump);
+                self.emit(Instruction::Pop, span);
+                self.tail_op(else_block)?;
+                self.patch(end_jump);
+            }
+
+            OpKind::Grouping(ref op) => self.tail_op(op)?,
+
+            OpKind::Block(ref statements) => {
+                self.begin_scope();
+                match statements.split_last() {
+                    Some((last, rest)) => {
+                        for statement in rest {
+                            self.statement(statement)?;
+                            self.emit(Instruction::Pop, &statement.span);
+                        }
+                        match last.kind {
+                            StatementKind::Op(ref op) => self.tail_op(op)?,
+                            _ => self.statement(last)?,
+                        }
+                    }
+                    None => self.constant(Primitive::None, span),
+                }
+                self.end_scope(span);
+            }
+
+            _ => self.op(op)?,
+        }
+        Ok(())
+    }
+
+    fn op(&mut self, op: &Op) -> Result<()> {
+        let span = &op.span;
+        match op.kind {
+            OpKind::Primary(ref literal) => self.literal(literal, span)?,
+
+            OpKind::Unary(ref operator, ref right) => {
+                self.literal(right, span)?;
+                match operator {
+                    Tok::Minus => self.emit(Instruction::Neg, span),
+                    Tok::Not => self.emit(Instruction::Not, span),
+                    _ => unreachable!(),
+                };
+            }
+
+            OpKind::Binary(ref left, ref operator @ (Tok::And | Tok::Or), ref right) => {
+                self.op(left)?;
+                let jump = match operator {
+                    Tok::And => self.emit(Instruction::JumpIfFalse(0), span),
+                    _ => self.emit(Instruction::JumpIfTrue(0), span),
+                };
+                self.emit(Instruction::Pop, span);
+                self.op(right)?;
+                self.patch(jump);
+            }
+
+            OpKind::Binary(ref left, ref operator, ref right) => {
+                self.op(left)?;
+                self.op(right)?;
+                let instruction = match operator {
+                    Tok::Plus => Instruction::Add,
+                    Tok::Minus => Instruction::Sub,
+                    Tok::Asterisk => Instruction::Mul,
+                    Tok::Slash => Instruction::Div,
+                    Tok::SlashSlash => Instruction::FloorDiv,
+                    Tok::Percent => Instruction::Rem,
+                    Tok::Pow => Instruction::Pow,
+                    Tok::Comp => Instruction::Equal,
+                    Tok::Different => Instruction::NotEqual,
+                    Tok::Gt => Instruction::Greater,
+                    Tok::GtOrEq => Instruction::GreaterEqual,
+                    Tok::Lt => Instruction::Less,
+                    Tok::LtOrEq => Instruction::LessEqual,
+                    _ => unreachable!(),
+                };
+                self.emit(instruction, span);
+            }
+
+            OpKind::Grouping(ref op) => self.op(op)?,
+
+            OpKind::List(ref items) => {
+                for item in items {
+                    self.op(item)?;
+                }
+                self.emit(Instruction::List(items.len()), span);
+            }
+
+            OpKind::Template(ref parts) => {
+                for part in parts {
+                    self.op(part)?;
+                }
+                self.emit(Instruction::Template(parts.len()), span);
+            }
+
+            OpKind::Map(ref entries) => {
+                for (key, value) in entries {
+                    self.constant(Primitive::Str(key.to_string()), span);
+                    self.op(value)?;
+                }
+                self.emit(Instruction::Map(entries.len()), span);
+            }
+
+            OpKind::Index(ref value, ref index) => {
+                self.op(value)?;
+                self.op(index)?;
+                self.emit(Instruction::Index, span);
+            }
+
+            OpKind::Slice(ref value, ref start, ref end) => {
+                self.op(value)?;
+                for bound in [start, end] {
+                    match bound {
+                        Some(bound) => self.op(bound)?,
+                        None => self.constant(Primitive::None, span),
+                    }
+                }
+                self.emit(Instruction::Slice, span);
+            }
+
+            OpKind::Lambda(ref params, _, ref body) => {
+                let params: Vec<String> = params.iter().map(|param| param.name.to_string()).collect();
+                self.closure("<lambda>", &params, body, span)?;
+            }
+
+            OpKind::Access(ref module, ref name) => {
+                self.op(module)?;
+                let name = self.name(name);
+                self.emit(Instruction::GetField(name), span);
+            }
+
+            OpKind::Call(ref called, ref arguments) => {
+                self.op(called)?;
+                for argument in arguments {
+                    self.op(argument)?;
+                }
+                self.emit(Instruction::Call(arguments.len()), span);
+            }
+
+            OpKind::Block(ref statements) => {
+                self.begin_scope();
+                self.sequence(statements, span)?;
+                self.end_scope(span);
+            }
+
+            OpKind::If(ref condition, ref block, ref else_block) => {
+                self.op(condition)?;
+                let else_jump = self.emit(Instruction::JumpIfFalse(0), span);
+                self.emit(Instruction::Pop, span);
+                self.op(block)?;
+                let end_jump = self.emit(Instruction::Jump(0), span);
+
+                // the condition is still on the stack when jumping to the else branch
+                self.patch(else_jump);
+                self.emit(Instruction::Pop, span);
+                self.op(else_block)?;
+                self.patch(end_jump);
+            }
+
+            OpKind::While(ref condition, ref body) => {
+                let start = self.begin_loop();
+                self.op(condition)?;
+                let exit = self.emit(Instruction::JumpIfFalse(0), span);
+                self.emit(Instruction::Pop, span);
+                self.op(body)?;
+                self.emit(Instruction::Pop, span);
+                self.emit(Instruction::Jump(start), span);
+
+                // the condition is still on the stack when the loop ends
+                self.patch(exit);
+                self.state().height += 1;
+                self.emit(Instruction::Pop, span);
+                self.end_loop();
+                self.constant(Primitive::None, span);
+            }
+
+            OpKind::For(ref name, ref iterable, ref body) => {
+                // the items and the position of the next one stay under the loop variable
+                self.op(iterable)?;
+                self.emit(Instruction::Iter, &iterable.span);
+                self.constant(Primitive::Int(0), span);
+
+                let start = self.begin_loop();
+                let next = self.emit(Instruction::ForNext(0), span);
+                self.begin_scope();
+                self.define(name, span);
+                self.op(body)?;
+                self.end_scope(span);
+                self.emit(Instruction::Pop, span);
+                self.emit(Instruction::Jump(start), span);
+
+                self.patch(next);
+                self.end_loop();
+                self.emit(Instruction::Drop(2), span);
+                self.constant(Primitive::None, span);
+            }
+
+            OpKind::Break => self.loop_jump(true, span),
+
+            OpKind::Continue => self.loop_jump(false, span),
+
+            OpKind::Return(ref value) => {
+                match value {
+                    Some(value) => self.tail_op(value)?,
+                    None => self.constant(Primitive::None, span),
+                }
+                self.emit(Instruction::Return, span);
+                // like `break`, the code after it is compiled as if it left a value
+                self.state().height += 1;
+            }
+
+            OpKind::Error => return Err(crate::error!(Runtime, span.clone(); "can't run code that failed to parse")),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,120 @@
+use crate::frontend::Span;
+use std::fmt;
+
+/// Which part of nolang raised the error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Lex,
+    Parse,
+    Type,
+    Reference,
+    Call,
+    Runtime,
+    Import,
+    StackOverflow,
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Lex => "LexError",
+            Self::Parse => "ParseError",
+            Self::Type => "TypeError",
+            Self::Reference => "ReferenceError",
+            Self::Call => "CallError",
+            Self::Runtime => "RuntimeError",
+            Self::Import => "ImportError",
+            Self::StackOverflow => "StackOverflowError",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Extra information attached to an error, like where a delimiter was opened
+#[derive(Debug, Clone, PartialEq)]
+pub struct Note {
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+/// A function call that was running when a runtime error happened
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub span: Span, // where the function was called
+}
+
+/// Error returned by the lexer, the parser and the interpreter
+#[derive(Debug, Clone, PartialEq)]
+pub struct NolangError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub span: Option<Span>,
+    pub notes: Vec<Note>,
+    pub trace: Box<[TraceFrame]>, // innermost call first, boxed to keep results small
+}
+
+pub type Result<T> = std::result::Result<T, NolangError>;
+
+impl NolangError {
+    pub fn new(kind: ErrorKind, message: String) -> Self {
+        Self {
+            kind,
+            message,
+            span: None,
+            notes: Vec::new(),
+            trace: Box::new([]),
+        }
+    }
+
+    /// Set where the error happened
+    pub fn at(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
+    }
+
+    /// Set where the error happened, if it wasn't set by a more specific place before
+    pub fn or_at(self, span: &Span) -> Self {
+        match self.span {
+            Some(_) => self,
+            None => self.at(span.clone()),
+        }
+    }
+
+    /// Attach a secondary note, pointing to `span` when there's one
+    pub fn with_note(mut self, message: &str, span: Option<Span>) -> Self {
+        self.notes.push(Note {
+            message: message.to_string(),
+            span,
+        });
+        self
+    }
+
+    /// The first of `errors` with the others attached as notes pointing to where they happened, for
+    /// places that return a single error. `errors` can't be empty
+    pub fn from_many(errors: Vec<NolangError>) -> Self {
+        let mut errors = errors.into_iter();
+        let first = errors.next().expect("no errors to report");
+        errors.fold(first, |error, other| {
+            error.with_note(&format!("{}: {}", other.kind, other.message), other.span)
+        })
+    }
+
+    /// Attach the function calls that were running when the error happened
+    pub fn with_trace(mut self, trace: Vec<TraceFrame>) -> Self {
+        self.trace = trace.into_boxed_slice();
+        self
+    }
+}
+
+impl fmt::Display for NolangError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)?;
+        if let Some(ref span) = self.span {
+            write!(f, " at {}", span)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for NolangError {}
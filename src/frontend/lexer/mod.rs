@@ -1,85 +1,179 @@
 #[macro_use]
 mod util;
 
-use super::tokens::{keyword_get_tok, Tokens as Tok};
+use super::tokens::{keyword_get_tok, TemplatePart, Token, Tokens as Tok};
+use super::Span;
+use crate::error::Result;
+use std::rc::Rc;
 use util::*;
 
 pub struct Lexer {
-    line: usize,
+    file: Rc<str>,
     pos: usize,
     raw: Vec<char>,
     ch: char,
+    offsets: Vec<usize>,     // byte offset of every char, plus the end of the input
+    line_starts: Vec<usize>, // char index where every line starts
 }
 
 impl Lexer {
-    pub fn lex(input: String) -> impl Iterator<Item = Tok> {
+    pub fn lex(input: String, file: &str) -> Result<impl Iterator<Item = Token>> {
+        let raw: Vec<char> = input.chars().collect();
+
+        let mut offsets: Vec<usize> = input.char_indices().map(|(offset, _)| offset).collect();
+        offsets.push(input.len());
+
+        let mut line_starts = vec![0];
+        line_starts.extend(raw.iter().enumerate().filter(|(_, ch)| **ch == '\n').map(|(pos, _)| pos + 1));
+
         let mut eself = Self {
-            ch: '|',
-            raw: input.chars().collect(),
+            file: Rc::from(file),
+            ch: raw.first().copied().unwrap_or('\0'),
+            raw,
             pos: 0,
-            line: 1,
+            offsets,
+            line_starts,
         };
 
-        eself.next();
         let mut vec_tok = vec![];
 
         while eself.pos < eself.raw.len() {
-            vec_tok.push(eself.get_tok());
+            let start = eself.pos;
+            if let Some(tok) = eself.get_tok()? {
+                vec_tok.push(Token {
+                    tok,
+                    span: eself.span(start, eself.pos + 1),
+                });
+            }
             eself.next();
         }
 
-        vec_tok.into_iter()
+        Ok(vec_tok.into_iter())
     }
 
-    fn next(&mut self) {
-        if self.pos < self.raw.len() {
-            self.ch = self.current();
-            self.pos += 1;
+    /// Build the span for the chars between `start` and `end` (exclusive)
+    fn span(&self, start: usize, end: usize) -> Span {
+        let end = end.min(self.raw.len());
+        let line = match self.line_starts.binary_search(&start) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+
+        Span {
+            file: self.file.clone(),
+            line: line + 1,
+            column: start - self.line_starts[line] + 1,
+            start: self.offsets[start],
+            end: self.offsets[end],
         }
     }
 
+    /// Span of the char being lexed
+    fn current_span(&self) -> Span {
+        self.span(self.pos, self.pos + 1)
+    }
+
+    fn next(&mut self) {
+        self.pos += 1;
+        self.ch = self.raw.get(self.pos).copied().unwrap_or('\0');
+    }
+
     fn peek(&self, ch: char) -> bool {
-        self.current() == ch
+        self.raw.get(self.pos + 1) == Some(&ch)
     }
 
-    fn current(&self) -> char {
-        self.raw[self.pos]
+    fn at_end(&self) -> bool {
+        self.pos >= self.raw.len()
     }
 
     fn back(&mut self) {
         self.pos -= 1;
-        self.ch = self.raw[self.pos - 1];
+        self.ch = self.raw[self.pos];
     }
 
-    fn ignore_comment(&mut self) -> Tok {
-        while self.ch != '\n' {
+    /// Skip until the end of the line, the newline itself is still lexed
+    fn ignore_comment(&mut self) {
+        while !self.peek('\n') && !self.at_end() {
             self.next();
         }
-        self.line += 1;
-        self.next();
-        self.get_tok()
     }
 
-    fn get_tok(&mut self) -> Tok {
-        match self.ch {
-            // Whitespaces
-            ' ' | '\r' | '\t' => {
-                self.next();
-                self.get_tok()
+    /// Lex the code of a `${...}` inside of a string, starting at the `$` and stopping at the `}`
+    /// that closes it. Newlines are skipped, the code is a single expression
+    fn interpolation(&mut self) -> Result<Vec<Token>> {
+        let start = self.pos;
+        self.next();
+        self.next();
+
+        let mut tokens = Vec::new();
+        let mut braces = 0;
+        loop {
+            if self.at_end() {
+                return Err(crate::error!(Lex, self.span(start, start + 2); "unclosed `${{` in string"));
+            }
+            match self.ch {
+                '}' if braces == 0 => break,
+                '{' => braces += 1,
+                '}' => braces -= 1,
+                _ => (),
             }
-            '\n' => {
-                self.line += 1;
-                Tok::Newline
+
+            let token_start = self.pos;
+            match self.get_tok()? {
+                Some(Tok::Newline) | None => (),
+                Some(tok) => tokens.push(Token {
+                    tok,
+                    span: self.span(token_start, self.pos + 1),
+                }),
             }
+            self.next();
+        }
+
+        if tokens.is_empty() {
+            return Err(crate::error!(Lex, self.span(start, self.pos + 1); "empty `${{}}` in string"));
+        }
+        Ok(tokens)
+    }
+
+    /// Lex the token starting at the current char, returns `None` for whitespace and comments
+    fn get_tok(&mut self) -> Result<Option<Tok>> {
+        let tok = match self.ch {
+            // Whitespaces
+            ' ' | '\r' | '\t' => return Ok(None),
+            '\n' => Tok::Newline,
 
             // Mathematical operators
+            '+' if self.peek('=') => {
+                self.next();
+                Tok::PlusAssign
+            }
             '+' => Tok::Plus,
+            '-' if self.peek('>') => {
+                self.next();
+                Tok::Arrow
+            }
+            '-' if self.peek('=') => {
+                self.next();
+                Tok::MinusAssign
+            }
             '-' => Tok::Minus,
             '*' if self.peek('*') => {
                 self.next();
                 Tok::Pow
             }
+            '*' if self.peek('=') => {
+                self.next();
+                Tok::AsteriskAssign
+            }
             '*' => Tok::Asterisk,
+            '/' if self.peek('=') => {
+                self.next();
+                Tok::SlashAssign
+            }
+            '/' if self.peek('/') => {
+                self.next();
+                Tok::SlashSlash
+            }
             '/' => Tok::Slash,
             '%' => Tok::Percent,
 
@@ -98,6 +192,10 @@ impl Lexer {
                 self.next();
                 Tok::Comp
             }
+            '=' if self.peek('>') => {
+                self.next();
+                Tok::FatArrow
+            }
             '=' => Tok::Assign,
 
             '~' if self.peek('=') => {
@@ -114,30 +212,79 @@ impl Lexer {
             }
             ';' => Tok::Semicolon,
             '.' => Tok::Point,
+            ':' => Tok::Colon,
 
             // Grouping
             '(' => Tok::Lparen,
             ')' => Tok::Rparen,
             '{' => Tok::Lbrace,
             '}' => Tok::Rbrace,
+            '[' => Tok::Lbracket,
+            ']' => Tok::Rbracket,
 
             // Comment
-            '@' => self.ignore_comment(),
+            '@' => {
+                self.ignore_comment();
+                return Ok(None);
+            }
 
             // Identifiers and constants
             '\'' | '"' => {
+                let start = self.pos;
                 let ch = self.ch;
                 self.next();
-                get_str!(self; ch != self.ch => str_vec);
-                self.next();
-                Tok::Str(str_vec)
+                get_str!(self, start; ch != self.ch => str_vec, parts);
+                match parts.is_empty() {
+                    true => Tok::Str(str_vec),
+                    false => {
+                        parts.push(TemplatePart::Str(str_vec));
+                        Tok::Template(parts)
+                    }
+                }
             }
             c if is_valid_math_symbol(&c) => {
+                let start = self.pos;
                 get_val!(self; is_valid_math_symbol(&self.ch) => num);
 
-                Tok::Num(num.parse::<f64>().unwrap_or_else(
-                    |_| crate::error!("LexerError"; "can't parse number {} at line {}", num, self.line => 1)
-                ))
+                // a suffix of `n` makes a big integer and `d` an exact decimal, like 100n or 19.99d
+                let suffix = match (self.raw.get(self.pos + 1), self.raw.get(self.pos + 2)) {
+                    (Some(&suffix @ ('n' | 'd')), after) if !after.is_some_and(is_ch_valid) => {
+                        self.next();
+                        Some(suffix)
+                    }
+                    _ => None,
+                };
+
+                if suffix == Some('n') {
+                    match num.parse() {
+                        Ok(num) => Tok::BigInt(num),
+                        Err(_) => {
+                            return Err(crate::error!(Lex, self.span(start, self.pos + 1); "can't parse big integer {}n", num))
+                        }
+                    }
+                } else if suffix == Some('d') {
+                    match num.parse() {
+                        Ok(num) => Tok::Decimal(num),
+                        Err(_) => {
+                            return Err(crate::error!(Lex, self.span(start, self.pos + 1); "can't parse decimal {}d", num))
+                        }
+                    }
+                } else if !num.contains('.') {
+                    // numbers without a `.` are integers
+                    match num.parse::<i64>() {
+                        Ok(num) => Tok::Int(num),
+                        Err(_) => {
+                            return Err(crate::error!(Lex, self.span(start, self.pos + 1); "integer {} doesn't fit in 64 bits", num))
+                        }
+                    }
+                } else {
+                    match num.parse::<f64>() {
+                        Ok(num) => Tok::Num(num),
+                        Err(_) => {
+                            return Err(crate::error!(Lex, self.span(start, self.pos + 1); "can't parse number {}", num))
+                        }
+                    }
+                }
             }
             c if is_ch_valid(&c) => {
                 get_val!(self; is_ch_valid(&self.ch) => ident);
@@ -149,9 +296,8 @@ impl Lexer {
             }
 
             // Nothing matches
-            c => {
-                crate::error!("LexerError"; "Unexpected token {} on line {}", c, self.line => 1)
-            }
-        }
+            c => return Err(crate::error!(Lex, self.current_span(); "Unexpected token {}", c)),
+        };
+        Ok(Some(tok))
     }
 }
@@ -6,34 +6,37 @@ pub fn is_valid_math_symbol(expr: &char) -> bool {
     expr.is_numeric() || expr == &'.'
 }
 
-#[macro_export]
+/// Collect chars while `$cond` holds, leaving the lexer at the last collected char
 macro_rules! get_val {
     ($self:expr; $cond:expr => $create:ident) => {
         let mut $create = String::new();
-        loop {
-            if !$cond {
-                break;
-            }
+        while $cond {
             $create.push($self.ch);
-            let pos = $self.pos;
             $self.next();
-
-            if pos == $self.pos {
-                crate::error!("ParserError"; "unclosed delimiter at line {}", $self.line => 1)
-            }
         }
         $self.back()
     };
 }
 
-#[macro_export]
+/// Collect the chars of a string literal until `$cond` fails, resolving escape sequences. The code
+/// of every `${...}` is lexed into `$parts`, with the text before it
 macro_rules! get_str {
-    ($self:expr; $cond:expr => $create:ident) => {
+    ($self:expr, $start:expr; $cond:expr => $create:ident, $parts:ident) => {
         let mut $create = String::new();
+        let mut $parts = Vec::new();
         loop {
+            if $self.at_end() {
+                return Err($crate::error!(Lex, $self.span($start, $self.pos); "unclosed string literal"));
+            }
             if !$cond {
                 break;
             }
+            if $self.ch == '$' && $self.peek('{') {
+                $parts.push(TemplatePart::Str(std::mem::take(&mut $create)));
+                $parts.push(TemplatePart::Code($self.interpolation()?));
+                $self.next();
+                continue;
+            }
             $create.push(match $self.ch {
                 '\\' => {
                     $self.next();
@@ -45,18 +48,15 @@ macro_rules! get_str {
                         '\\' => '\\',
                         '\'' => '\'',
                         '"' => '"',
-                        c => crate::error!("StrError"; "unkown escape sequence '\\{}' on line {}", c, $self.line => 1)
+                        '$' => '$',
+                        c => {
+                            return Err($crate::error!(Lex, $self.span($self.pos - 1, $self.pos + 1); "unknown escape sequence '\\{}'", c))
+                        }
                     }
                 },
                 ch => ch
             });
-            let pos = $self.pos;
             $self.next();
-
-            if pos == $self.pos {
-                crate::error!("ParserError"; "unclosed delimiter at line {}", $self.line => 1)
-            }
         }
-        $self.back()
     };
 }
@@ -1,18 +1,34 @@
 mod lexer;
 mod parser;
+mod span;
 mod tokens;
 
+use crate::error::{NolangError, Result};
 use lexer::Lexer;
 use parser::Parser;
 pub use {
-    parser::{Literal, Op, Statement},
-    tokens::Tokens,
+    parser::{Literal, Op, OpKind, Param, Statement, StatementKind, Type},
+    span::Span,
+    tokens::{Token, Tokens},
 };
 
-pub fn parse(input: String) -> impl Iterator<Item = Statement> {
-    Parser::parse(Lexer::lex(input))
+/// Parse the input, returning every syntax error found when it's not valid
+pub fn parse(input: String, file: &str) -> std::result::Result<impl Iterator<Item = Statement>, Vec<NolangError>> {
+    match parse_partial(input, file) {
+        (statements, errors) if errors.is_empty() => Ok(statements.into_iter()),
+        (_, errors) => Err(errors),
+    }
 }
 
-pub fn lex(input: String) -> impl Iterator<Item = Tokens> {
-    Lexer::lex(input)
+/// Parse the input, returning the syntax errors together with the AST. Broken parts of the code
+/// are marked with `OpKind::Error`, so tools can keep working on code that doesn't parse
+pub fn parse_partial(input: String, file: &str) -> (Vec<Statement>, Vec<NolangError>) {
+    match Lexer::lex(input, file) {
+        Ok(tokens) => Parser::parse(tokens),
+        Err(e) => (Vec::new(), vec![e]),
+    }
+}
+
+pub fn lex(input: String, file: &str) -> Result<impl Iterator<Item = Token>> {
+    Lexer::lex(input, file)
 }
@@ -1,4 +1,6 @@
 use super::Op;
+use bigdecimal::{BigDecimal, Zero};
+use num_bigint::BigInt;
 
 #[derive(Debug, Clone)]
 pub enum Literal {
@@ -6,17 +8,19 @@ pub enum Literal {
     String(String),
     Bool(bool),
     Num(f64),
+    Int(i64),
+    BigInt(BigInt),
+    Decimal(BigDecimal),
     Operation(Op),
     None,
 }
 
 impl Literal {
     pub fn boolean(&self) -> bool {
-        match *self {
-            Self::Bool(false) => false,
-            Self::None => false,
-            Self::Num(x) if x == 0.0 => false,
-            _ => true,
+        match self {
+            Self::BigInt(n) => !n.is_zero(),
+            Self::Decimal(n) => !n.is_zero(),
+            _ => !matches!(*self, Self::Bool(false) | Self::None | Self::Num(0.0) | Self::Int(0)),
         }
     }
 }
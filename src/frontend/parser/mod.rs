@@ -1,28 +1,33 @@
 mod literal;
 mod op;
 mod statement;
+mod types;
 
-use super::tokens::Tokens as Tok;
+use super::tokens::{TemplatePart, Token, Tokens as Tok};
+use super::Span;
+use crate::error::{NolangError, Result};
 pub use literal::Literal;
-pub use op::Op;
-pub use statement::Statement;
+pub use op::{Op, OpKind};
+pub use statement::{Param, Statement, StatementKind};
+pub use types::Type;
 
-/// Check if a token matches and panic if it doesn't, returns ()
-macro_rules! consume {
-    ($current: expr, $( $tokens:pat )|+) => {{
+/// Check if a token matches and return a ParseError if it doesn't
+macro_rules! expect {
+    ($self: ident, $current: expr, $( $tokens:pat )|+) => {{
         let printable = format!("{:?}", [ $(stringify!($tokens).split("::").last().unwrap()),+ ]).replace("\"", "")
             .replace("[", "")
             .replace("]", "");
         if !matches!($current, $($tokens)|+) {
-            crate::error!("ParseError"; "expected one of: {} found {:?}", printable, $current => 1)
+            return Err(crate::error!(Parse, $self.span.clone(); "expected one of: {} found {:?}", printable, $current));
         }
-        true
     }};
+}
 
+/// Same as `expect!`, but also advances to the next token
+macro_rules! consume {
     ($self: ident, $current: expr, $($tokens:pat)|+) => {{
-        consume!($current, $($tokens)|+);
+        expect!($self, $current, $($tokens)|+);
         $self.next();
-        true
     }}
 }
 
@@ -30,41 +35,115 @@ macro_rules! consume {
 pub struct Parser {
     index: isize,
     current: Tok,
-    tokens: Vec<Tok>,
-    line: usize,
+    span: Span,     // span of the current token
+    previous: Span, // span of the last token consumed that wasn't a newline
+    tokens: Vec<Token>,
+    errors: Vec<NolangError>,
+    depth: usize, // how many blocks and function bodies the parser is in
+    loops: usize, // how many loops the parser is in, since the last function body
+    functions: usize, // how many function bodies the parser is in
 }
 
 impl Parser {
-    /// Simple implementation of a parser
-    pub fn parse(tokens: impl Iterator<Item = Tok>) -> impl Iterator<Item = Statement> {
+    /// Simple implementation of a parser. Syntax errors don't stop the parsing, the broken
+    /// statements are replaced by `OpKind::Error` and every error is returned with the AST
+    pub fn parse(tokens: impl Iterator<Item = Token>) -> (Vec<Statement>, Vec<NolangError>) {
         let mut eself = Self {
             index: -1,
             current: Tok::None,
+            span: Span::default(),
+            previous: Span::default(),
             tokens: tokens.collect(),
-            line: 1,
+            errors: Vec::new(),
+            depth: 0,
+            loops: 0,
+            functions: 0,
         };
         eself.next();
 
         let mut staments_vec: Vec<Statement> = vec![];
 
-        while (eself.index as usize) < eself.tokens.len() {
-            staments_vec.push(eself.statement());
-            consume!(eself, eself.current, Tok::Eof | Tok::Newline | Tok::Semicolon);
+        loop {
+            while matches!(eself.current, Tok::Newline | Tok::Semicolon) {
+                eself.next()
+            }
+            match eself.current {
+                Tok::Eof => break,
+                Tok::End => {
+                    let error = crate::error!(Parse, eself.span.clone(); "unexpected `end` outside of a do block");
+                    eself.errors.push(error);
+                    eself.next();
+                }
+                _ => {
+                    let statement = eself.statement_or_error(true);
+                    staments_vec.push(statement)
+                }
+            }
         }
 
-        staments_vec.into_iter()
+        (staments_vec, eself.errors)
+    }
+
+    /// Parse a statement. On errors, the error is saved and the parser skips to the start of the
+    /// next statement, returning an error node in place of the broken one
+    fn statement_or_error(&mut self, top_level: bool) -> Statement {
+        let start = self.span.clone();
+        let result = self.statement().and_then(|statement| {
+            if top_level {
+                self.end_statement()?;
+            }
+            Ok(statement)
+        });
+
+        match result {
+            Ok(statement) => statement,
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+
+                if top_level {
+                    self.depth = 0;
+                    self.loops = 0;
+                    self.functions = 0;
+                }
+
+                // an `end` right after the error most likely closes the broken statement
+                if top_level && matches!(self.current, Tok::End) {
+                    self.next();
+                }
+
+                let span = self.span_from(&start);
+                Statement::new(StatementKind::Op(Op::new(OpKind::Error, span.clone())), span)
+            }
+        }
+    }
+
+    /// Skip tokens until one that can end a statement
+    fn synchronize(&mut self) {
+        while !matches!(self.current, Tok::Newline | Tok::Semicolon | Tok::End | Tok::Eof) {
+            self.next()
+        }
     }
 
     /// Consume one token, advancing the self.current by one position
     fn next(&mut self) {
+        if !matches!(self.current, Tok::Newline) {
+            self.previous = self.span.clone();
+        }
+
         self.index += 1;
-        if (self.index as usize) < self.tokens.len() {
-            if self.tokens[self.index as usize] == Tok::Newline {
-                self.line += 1;
+        match self.tokens.get(self.index as usize) {
+            Some(token) => {
+                self.current = token.tok.clone();
+                self.span = token.span.clone();
+            }
+            None => {
+                self.current = Tok::Eof;
+                self.span = Span {
+                    start: self.previous.end,
+                    ..self.previous.clone()
+                };
             }
-            self.current = self.tokens[self.index as usize].clone()
-        } else {
-            self.current = Tok::Eof
         }
     }
 
@@ -77,31 +156,92 @@ impl Parser {
         }
     }
 
+    /// Span going from `start` until the last consumed token
+    fn span_from(&self, start: &Span) -> Span {
+        start.to(&self.previous)
+    }
+
+    /// The token after the current one
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get((self.index + 1) as usize).map(|t| &t.tok)
+    }
+
+    /// Check if a newline was skipped by `next_skip` right before the current token
+    fn after_newline(&self) -> bool {
+        self.index > 0
+            && matches!(
+                self.tokens.get(self.index as usize - 1).map(|t| &t.tok),
+                Some(Tok::Newline)
+            )
+    }
+
+    /// Check that the statement ended, a newline skipped by `next_skip` also counts as the end
+    fn end_statement(&mut self) -> Result<()> {
+        if !self.after_newline() {
+            expect!(self, self.current, Tok::Eof | Tok::Newline | Tok::Semicolon);
+        }
+        Ok(())
+    }
 
     // Statements region
-    fn statement(&mut self) -> Statement {
+    fn statement(&mut self) -> Result<Statement> {
         while matches!(self.current, Tok::Newline | Tok::Semicolon) {
             self.next()
         }
         match self.current {
             Tok::Let => self.assign_stat(),
+            Tok::Ident(_) if self.peek().is_some_and(Tok::is_assignment) => self.reassign_stat(),
             Tok::Defn => self.defn_stat(),
-            _ => Statement::Op(self.operation())
+            Tok::Import => self.import_stat(),
+            Tok::Export => self.export_stat(),
+            _ => {
+                let op = self.operation()?;
+                let span = op.span.clone();
+                Ok(Statement::new(StatementKind::Op(op), span))
+            }
         }
     }
 
-    fn defn_stat(&mut self) -> Statement {
+    fn defn_stat(&mut self) -> Result<Statement> {
+        let start = self.span.clone();
         consume!(self, self.current, Tok::Defn);
+        let (arguments, return_type) = self.signature()?;
+
+        let name = match &self.current {
+            Tok::Ident(id) => id.to_string(),
+            e => return Err(crate::error!(Parse, self.span.clone(); "expected ident after function declaration, found {}", e)),
+        };
+        self.next_skip();
+        consume!(self, self.current, Tok::Assign);
+        self.depth += 1;
+        self.functions += 1;
+        let loops = std::mem::replace(&mut self.loops, 0); // loops don't continue inside of functions
+        let block = self.statement();
+        self.loops = loops;
+        self.functions -= 1;
+        self.depth -= 1;
+        let block = Box::new(block?);
+
+        Ok(Statement::new(
+            StatementKind::FuncAssign(name, arguments, return_type, block),
+            self.span_from(&start),
+        ))
+    }
+
+    /// Parse the parameters and the optional return type of a function, `(a, b: Num) -> Num`
+    fn signature(&mut self) -> Result<(Vec<Param>, Option<Type>)> {
         consume!(self, self.current, Tok::Lparen);
 
         let mut arguments = Vec::new();
 
         while !matches!(self.current, Tok::Rparen) {
-            arguments.push(match &self.current {
+            let name = match &self.current {
                 Tok::Ident(id) => id.to_string(),
-                _ => crate::error!("ParseError"; "Expected variable name after function on line {}", self.line => 1),
-            });
+                _ => return Err(crate::error!(Parse, self.span.clone(); "Expected variable name after function")),
+            };
             self.next_skip();
+            let annotation = self.annotation()?;
+            arguments.push(Param { name, annotation });
 
             if !matches!(self.current, Tok::Rparen) {
                 consume!(self, self.current, Tok::Comma);
@@ -109,114 +249,517 @@ impl Parser {
         }
         consume!(self, self.current, Tok::Rparen);
 
-        let name = match &self.current {
-            Tok::Ident(id) => id.to_string(),
-            e => crate::error!("ParseError"; "expected ident after function declaration, found {}, on line {}", e, self.line => 1)
+        let return_type = match self.current {
+            Tok::Arrow => {
+                self.next_skip();
+                Some(self.type_expr()?)
+            }
+            _ => None,
         };
-        self.next_skip();
-        consume!(self, self.current, Tok::Assign);
-        let block = Box::new(self.statement());
 
-        Statement::FuncAssign(name, arguments, block)
+        Ok((arguments, return_type))
     }
 
-    fn assign_stat(&mut self) -> Statement {
+    fn assign_stat(&mut self) -> Result<Statement> {
+        let start = self.span.clone();
         self.next_skip();
-        consume!(self.current, Tok::Ident(..));
+        expect!(self, self.current, Tok::Ident(..));
 
         let var_name = match &self.current {
             Tok::Ident(id) => id.to_string(),
             _ => unreachable!(),
         };
         self.next_skip();
+        let annotation = self.annotation()?;
+
+        consume!(self, self.current, Tok::Assign);
+        let value = self.operation()?;
+
+        Ok(Statement::new(
+            StatementKind::Assign(var_name, annotation, Box::new(value)),
+            self.span_from(&start),
+        ))
+    }
+
+    /// `x = value`, or a compound assignment like `x += value`, to a variable that already exists
+    fn reassign_stat(&mut self) -> Result<Statement> {
+        let start = self.span.clone();
+        let name = match &self.current {
+            Tok::Ident(id) => id.to_string(),
+            _ => unreachable!(),
+        };
+        self.next();
+
+        let operator = match self.current {
+            Tok::PlusAssign => Some(Tok::Plus),
+            Tok::MinusAssign => Some(Tok::Minus),
+            Tok::AsteriskAssign => Some(Tok::Asterisk),
+            Tok::SlashAssign => Some(Tok::Slash),
+            _ => None,
+        };
+        self.next_skip();
+        let mut value = self.operation()?;
+
+        if let Some(operator) = operator {
+            let variable = Op::new(OpKind::Primary(Box::new(Literal::VarNormal(name.to_string()))), start.clone());
+            let span = self.span_from(&start);
+            value = Op::new(OpKind::Binary(Box::new(variable), operator, Box::new(value)), span);
+        }
+
+        Ok(Statement::new(
+            StatementKind::Reassign(name, Box::new(value)),
+            self.span_from(&start),
+        ))
+    }
+
+    /// `import "path"` or `import name, other from "path"`
+    fn import_stat(&mut self) -> Result<Statement> {
+        let start = self.span.clone();
+        self.next_skip();
+
+        let mut names = Vec::new();
+        if !matches!(self.current, Tok::Str(..)) {
+            loop {
+                match &self.current {
+                    Tok::Ident(id) => names.push(id.to_string()),
+                    e => return Err(crate::error!(Parse, self.span.clone(); "expected a name to import, found `{}`", e)),
+                }
+                self.next_skip();
+
+                if !matches!(self.current, Tok::Comma) {
+                    break;
+                }
+                self.next_skip();
+            }
+            consume!(self, self.current, Tok::From);
+        }
+
+        let path = match &self.current {
+            Tok::Str(path) => path.to_string(),
+            e => return Err(crate::error!(Parse, self.span.clone(); "expected the path of the module, found `{}`", e)),
+        };
+        self.next();
+
+        Ok(Statement::new(StatementKind::Import(path, names), self.span_from(&start)))
+    }
+
+    /// `export let ...` or `export defn ...`, only allowed at the top level of a file
+    fn export_stat(&mut self) -> Result<Statement> {
+        let start = self.span.clone();
+        if self.depth > 0 {
+            return Err(crate::error!(Parse, start; "`export` is only allowed at the top level of a module"));
+        }
+        self.next_skip();
 
-        if consume!(self, self.current, Tok::Assign) {
-            let value = self.operation();
+        expect!(self, self.current, Tok::Let | Tok::Defn);
+        let statement = self.statement()?;
 
-            Statement::Assign(var_name, Box::new(value))
-        } else { unreachable!() }
+        Ok(Statement::new(StatementKind::Export(Box::new(statement)), self.span_from(&start)))
     }
     // End statements region
 
+    // Types region
+    /// Parse an optional `: <Type>` annotation
+    fn annotation(&mut self) -> Result<Option<Type>> {
+        match self.current {
+            Tok::Colon => {
+                self.next_skip();
+                Ok(Some(self.type_expr()?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse a type, like `Num` or `(Num, Str) -> Bool`
+    fn type_expr(&mut self) -> Result<Type> {
+        let ty = match &self.current {
+            Tok::Ident(name) => match name.as_str() {
+                "Num" => Type::Num,
+                "Int" => Type::Int,
+                "BigInt" => Type::BigInt,
+                "Decimal" => Type::Decimal,
+                "Str" => Type::Str,
+                "Bool" => Type::Bool,
+                "List" => Type::List,
+                "Map" => Type::Map,
+                "Any" => Type::Any,
+                _ => return Err(crate::error!(Parse, self.span.clone(); "unknown type `{}`", name)),
+            },
+            Tok::None => Type::None,
+            Tok::Lparen => {
+                self.next_skip();
+                let mut params = Vec::new();
+                while !matches!(self.current, Tok::Rparen) {
+                    params.push(self.type_expr()?);
+                    if !matches!(self.current, Tok::Rparen) {
+                        consume!(self, self.current, Tok::Comma);
+                    }
+                }
+                self.next_skip();
+                consume!(self, self.current, Tok::Arrow);
+                return Ok(Type::Function(params, Box::new(self.type_expr()?)));
+            }
+            e => return Err(crate::error!(Parse, self.span.clone(); "expected a type, found `{}`", e)),
+        };
+        self.next_skip();
+        Ok(ty)
+    }
+    // End types region
+
     // Operations Region
-    fn if_op(&mut self) -> Op {
+    fn if_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
         self.next_skip(); // skips the current `if` toke
-        let condition = self.operation();
+        let condition = self.operation()?;
         consume!(self, self.current, Tok::Then);
-        let body = self.operation();
+        let body = self.operation()?;
 
-        match self.current {
+        while matches!(self.current, Tok::Newline) {
+            self.next()
+        }
+
+        let else_body = match self.current {
             Tok::Else => {
                 self.next_skip();
-                let else_body = self.operation();
-                Op::If(Box::new(condition), Box::new(body), Box::new(else_body))
+                self.operation()?
             }
-            Tok::Elif => Op::If(Box::new(condition), Box::new(body), Box::new(self.if_op())),
-            _ => crate::error!("ParseError"; "expected `else` after if" => 1),
-        }
+            Tok::Elif => self.if_op()?,
+            _ => return Err(crate::error!(Parse, self.span.clone(); "expected `else` after if")),
+        };
+
+        Ok(Op::new(
+            OpKind::If(Box::new(condition), Box::new(body), Box::new(else_body)),
+            self.span_from(&start),
+        ))
     }
 
-    fn block_op(&mut self) -> Op {
-        let line = self.line;
+    fn block_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
         let mut vec_stat = vec![];
         self.next_skip();
+        self.depth += 1;
 
-        while !matches!(self.current, Tok::End) {
-            vec_stat.push(self.statement());
-
-            if matches!(self.current, Tok::Eof) {
-                crate::error!("ParseError"; "unclosed do block opened on line {}", line => 1)
+        loop {
+            while matches!(self.current, Tok::Newline | Tok::Semicolon) {
+                self.next()
+            }
+            match self.current {
+                Tok::End => break,
+                Tok::Eof => {
+                    return Err(crate::error!(Parse, self.span.clone(); "unclosed do block, expected `end`")
+                        .with_note("do block opened here", Some(start)))
+                }
+                _ => vec_stat.push(self.statement_or_error(false)),
             }
         }
         consume!(self, self.current, Tok::End);
-        consume!(self, self.current, Tok::Semicolon | Tok::Newline | Tok::Eof);
+        self.depth -= 1;
+
+        Ok(Op::new(OpKind::Block(vec_stat), self.span_from(&start)))
+    }
+
+    /// The `do ... end` body of a loop
+    fn loop_body(&mut self) -> Result<Op> {
+        while matches!(self.current, Tok::Newline) {
+            self.next()
+        }
+        if !matches!(self.current, Tok::Do) {
+            return Err(crate::error!(Parse, self.span.clone(); "expected `do` to start the loop body, found {}", self.current));
+        }
+        self.loops += 1;
+        let body = self.block_op();
+        self.loops -= 1;
+        body
+    }
 
-        Op::Block(vec_stat)
+    /// `while <cond> do ... end`
+    fn while_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
+        consume!(self, self.current, Tok::While);
+        let condition = self.operation()?;
+        let body = self.loop_body()?;
+
+        Ok(Op::new(
+            OpKind::While(Box::new(condition), Box::new(body)),
+            self.span_from(&start),
+        ))
+    }
+
+    /// `for <name> in <iterable> do ... end`
+    fn for_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
+        consume!(self, self.current, Tok::For);
+        let name = match &self.current {
+            Tok::Ident(id) => id.to_string(),
+            e => return Err(crate::error!(Parse, self.span.clone(); "expected the loop variable after `for`, found {}", e)),
+        };
+        self.next_skip();
+        consume!(self, self.current, Tok::In);
+        let iterable = self.operation()?;
+        let body = self.loop_body()?;
+
+        Ok(Op::new(
+            OpKind::For(name, Box::new(iterable), Box::new(body)),
+            self.span_from(&start),
+        ))
+    }
+
+    /// `break` and `continue`, only allowed inside of a loop
+    fn jump_op(&mut self) -> Result<Op> {
+        let (kind, keyword) = match self.current {
+            Tok::Break => (OpKind::Break, "break"),
+            _ => (OpKind::Continue, "continue"),
+        };
+        if self.loops == 0 {
+            return Err(crate::error!(Parse, self.span.clone(); "`{}` outside of a loop", keyword));
+        }
+        let span = self.span.clone();
+        self.next();
+        Ok(Op::new(kind, span))
+    }
+
+    /// `return` or `return <value>`, only allowed inside of a function
+    fn return_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
+        if self.functions == 0 {
+            return Err(crate::error!(Parse, start; "`return` outside of a function"));
+        }
+        self.next();
+
+        // the value is optional, so anything that can end an operation means there's none
+        let value = match self.current {
+            Tok::Newline | Tok::Semicolon | Tok::End | Tok::Eof | Tok::Else | Tok::Elif => None,
+            Tok::Rparen | Tok::Rbracket | Tok::Rbrace | Tok::Comma => None,
+            _ => Some(Box::new(self.operation()?)),
+        };
+
+        Ok(Op::new(OpKind::Return(value), self.span_from(&start)))
+    }
+
+    /// Anonymous function, `fn(x, y) => x + y`
+    fn lambda_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
+        consume!(self, self.current, Tok::Fn);
+        let (params, return_type) = self.signature()?;
+        consume!(self, self.current, Tok::FatArrow);
+
+        self.depth += 1;
+        self.functions += 1;
+        let loops = std::mem::replace(&mut self.loops, 0);
+        let body = self.operation();
+        self.loops = loops;
+        self.functions -= 1;
+        self.depth -= 1;
+        let body = body?;
+        let span = body.span.clone();
+
+        Ok(Op::new(
+            OpKind::Lambda(params, return_type, Box::new(Statement::new(StatementKind::Op(body), span))),
+            self.span_from(&start),
+        ))
     }
 
     /// Check what's the current Op
-    fn operation(&mut self) -> Op {
+    fn operation(&mut self) -> Result<Op> {
         match self.current {
             Tok::If => self.if_op(),
             Tok::Do => self.block_op(),
-            _ => self.equality_op()
+            Tok::Fn => self.lambda_op(),
+            Tok::While => self.while_op(),
+            Tok::For => self.for_op(),
+            Tok::Break | Tok::Continue => self.jump_op(),
+            Tok::Return => self.return_op(),
+            _ => self.equality_op(),
         }
     }
 
     /// Get raw operations
-    fn primary_op(&mut self) -> Op {
+    fn primary_op(&mut self) -> Result<Op> {
         while matches!(self.current, Tok::Newline | Tok::Semicolon) {
             self.next()
         }
+        let start = self.span.clone();
         let literal: Literal = match &self.current {
             Tok::True => Literal::Bool(true),
             Tok::False => Literal::Bool(false),
             Tok::None => Literal::None,
             Tok::Num(n) => Literal::Num(*n),
+            Tok::Int(n) => Literal::Int(*n),
+            Tok::BigInt(n) => Literal::BigInt(n.clone()),
+            Tok::Decimal(n) => Literal::Decimal(n.clone()),
             Tok::Str(s) => Literal::String(s.to_string()),
             Tok::Ident(id) => Literal::VarNormal(id.to_string()),
+            Tok::Template(parts) => return self.template_op(parts.clone()),
+            Tok::Lbracket => return self.list_op(),
+            Tok::Lbrace => return self.map_op(),
             Tok::Lparen => {
                 self.next_skip();
-                let operation = self.operation();
-                consume!(self, self.current, Tok::Rparen);
-                return Op::Grouping(Box::new(operation));
+                let operation = self.operation()?;
+                if !matches!(self.current, Tok::Rparen) {
+                    return Err(crate::error!(Parse, self.span.clone(); "expected `)`, found `{}`", self.current)
+                        .with_note("parenthesis opened here", Some(start)));
+                }
+                self.next();
+                return Ok(Op::new(OpKind::Grouping(Box::new(operation)), self.span_from(&start)));
             }
 
-            e => crate::error!("ParseError"; "Unexpected `{}` on line {}", e, self.line => 1),
+            e => return Err(crate::error!(Parse, self.span.clone(); "Unexpected `{}`", e)),
         };
         self.next_skip();
-        Op::Primary(Box::new(literal))
+        Ok(Op::new(OpKind::Primary(Box::new(literal)), start))
+    }
+
+    /// String with `${...}`, the code of every interpolation is parsed as one operation
+    fn template_op(&mut self, parts: Vec<TemplatePart>) -> Result<Op> {
+        let start = self.span.clone();
+
+        let mut ops = Vec::with_capacity(parts.len());
+        for part in parts {
+            match part {
+                TemplatePart::Str(s) if s.is_empty() => (),
+                TemplatePart::Str(s) => ops.push(Op::new(OpKind::Primary(Box::new(Literal::String(s))), start.clone())),
+                TemplatePart::Code(tokens) => {
+                    let mut parser = Self {
+                        index: -1,
+                        current: Tok::None,
+                        span: start.clone(),
+                        previous: start.clone(),
+                        tokens,
+                        errors: Vec::new(),
+                        depth: self.depth,
+                        loops: self.loops,
+                        functions: self.functions,
+                    };
+                    parser.next();
+
+                    // errors of statements in blocks inside of the template are saved by the inner parser
+                    let op = parser.operation();
+                    self.errors.append(&mut parser.errors);
+                    ops.push(op?);
+                    if !matches!(parser.current, Tok::Eof) {
+                        return Err(crate::error!(Parse, parser.span.clone(); "expected `}}` after the value in the string, found `{}`", parser.current));
+                    }
+                }
+            }
+        }
+        self.next_skip();
+
+        Ok(Op::new(OpKind::Template(ops), start))
+    }
+
+    /// List literal, `[1, 2, 3]`
+    fn list_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
+        self.next_skip();
+
+        let mut items = Vec::new();
+        while !matches!(self.current, Tok::Rbracket) {
+            items.push(self.operation()?);
+
+            if matches!(self.current, Tok::Rbracket) {
+                break;
+            }
+            consume!(self, self.current, Tok::Comma);
+            while matches!(self.current, Tok::Newline) {
+                self.next()
+            }
+        }
+        self.next_skip();
+
+        Ok(Op::new(OpKind::List(items), self.span_from(&start)))
+    }
+
+    /// Map literal, `{ "name": "nolang", count: 1 }`. Keys are strings or names
+    fn map_op(&mut self) -> Result<Op> {
+        let start = self.span.clone();
+        self.next_skip();
+
+        let mut entries = Vec::new();
+        while !matches!(self.current, Tok::Rbrace) {
+            let key = match &self.current {
+                Tok::Str(key) | Tok::Ident(key) => key.to_string(),
+                e => return Err(crate::error!(Parse, self.span.clone(); "expected a map key, found `{}`", e)),
+            };
+            self.next_skip();
+            consume!(self, self.current, Tok::Colon);
+            while matches!(self.current, Tok::Newline) {
+                self.next()
+            }
+            entries.push((key, self.operation()?));
+
+            if matches!(self.current, Tok::Rbrace) {
+                break;
+            }
+            consume!(self, self.current, Tok::Comma);
+            while matches!(self.current, Tok::Newline) {
+                self.next()
+            }
+        }
+        self.next_skip();
+
+        Ok(Op::new(OpKind::Map(entries), self.span_from(&start)))
     }
 
-    fn call_op(&mut self) -> Op {
-        let mut called = self.primary_op();
+    /// Index or slice, the `[` was already consumed
+    fn index_op(&mut self, value: Op) -> Result<Op> {
+        let value_span = value.span.clone();
+        let start = match self.current {
+            Tok::Colon => None,
+            _ => Some(Box::new(self.operation()?)),
+        };
+
+        let kind = match self.current {
+            Tok::Colon => {
+                self.next_skip();
+                let end = match self.current {
+                    Tok::Rbracket => None,
+                    _ => Some(Box::new(self.operation()?)),
+                };
+                OpKind::Slice(Box::new(value), start, end)
+            }
+            _ => match start {
+                Some(index) => OpKind::Index(Box::new(value), index),
+                None => unreachable!(),
+            },
+        };
+
+        expect!(self, self.current, Tok::Rbracket);
+        self.next();
+        Ok(Op::new(kind, self.span_from(&value_span)))
+    }
+
+    /// Get calls, indexes and accesses to the names of a module, like `f(x)`, `list[0]` and
+    /// `module.name`
+    fn call_op(&mut self) -> Result<Op> {
+        let mut called = self.primary_op()?;
+
+        // a `(` or `[` in the next line starts a new statement instead of calling the value
+        while matches!(self.current, Tok::Point)
+            || matches!(self.current, Tok::Lparen | Tok::Lbracket) && !self.after_newline()
+        {
+            if matches!(self.current, Tok::Lbracket) {
+                self.next_skip();
+                called = self.index_op(called)?;
+                continue;
+            }
+
+            if matches!(self.current, Tok::Point) {
+                self.next_skip();
+                let name = match &self.current {
+                    Tok::Ident(id) => id.to_string(),
+                    e => return Err(crate::error!(Parse, self.span.clone(); "expected a name after `.`, found `{}`", e)),
+                };
+                self.next_skip();
+                let span = self.span_from(&called.span);
+                called = Op::new(OpKind::Access(Box::new(called), name), span);
+                continue;
+            }
 
-        while matches!(self.current, Tok::Lparen) {
             self.next_skip();
 
             let mut arguments = Vec::new();
             while !matches!(self.current, Tok::Rparen) {
-                arguments.push(self.operation());
+                arguments.push(self.operation()?);
 
                 if matches!(self.current, Tok::Rparen) {
                     break;
@@ -225,111 +768,122 @@ impl Parser {
                 consume!(self, self.current, Tok::Comma);
             }
             self.next_skip();
-            match called {
-                Op::Primary(ref p) => match **p {
+            match called.kind {
+                OpKind::Primary(ref p) => match **p {
                     Literal::VarNormal(..) => (),
-                    _ => crate::error!("TypeError"; "Can't call `{:?}`", p => 1),
+                    _ => return Err(crate::error!(Type, called.span.clone(); "Can't call `{:?}`", p)),
                 },
-                Op::Call(..) => (),
-                _ => crate::error!("TypeError"; "Can't call `{:?}`", called => 1),
+                OpKind::Call(..) | OpKind::Access(..) | OpKind::Grouping(..) | OpKind::Index(..) => (),
+                _ => return Err(crate::error!(Type, called.span.clone(); "Can't call `{:?}`", called.kind)),
             }
-            called = Op::Call(Box::new(called), arguments)
+            let span = self.span_from(&called.span);
+            called = Op::new(OpKind::Call(Box::new(called), arguments), span)
         }
-        called
+        Ok(called)
     }
 
     /// Get unary operations, such as `not(<OP>)` and `-<OP>`
-    fn unary_op(&mut self) -> Op {
+    fn unary_op(&mut self) -> Result<Op> {
         if matches!(self.current, Tok::Minus | Tok::Not) {
+            let start = self.span.clone();
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.call_op();
-            Op::Unary(operator, Box::new(Literal::Operation(right)))
+            let right = self.call_op()?;
+            Ok(Op::new(
+                OpKind::Unary(operator, Box::new(Literal::Operation(right))),
+                self.span_from(&start),
+            ))
         } else {
             self.call_op()
         }
     }
 
+    /// Build a binary operation spanning both sides
+    fn binary(&self, left: Op, operator: Tok, right: Op) -> Op {
+        let span = self.span_from(&left.span);
+        Op::new(OpKind::Binary(Box::new(left), operator, Box::new(right)), span)
+    }
+
     /// Get multiply and division operations
-    fn factor_op(&mut self) -> Op {
-        let mut left = self.unary_op();
+    fn factor_op(&mut self) -> Result<Op> {
+        let mut left = self.unary_op()?;
 
-        while matches!(self.current, Tok::Asterisk | Tok::Slash | Tok::Percent | Tok::Pow) {
+        while matches!(self.current, Tok::Asterisk | Tok::Slash | Tok::SlashSlash | Tok::Percent | Tok::Pow) {
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.unary_op();
+            let right = self.unary_op()?;
 
-            left = Op::Binary(Box::new(left), operator, Box::new(right))
+            left = self.binary(left, operator, right)
         }
-        left
+        Ok(left)
     }
 
     /// Get add and sub operations
-    fn term_op(&mut self) -> Op {
-        let mut left = self.factor_op();
+    fn term_op(&mut self) -> Result<Op> {
+        let mut left = self.factor_op()?;
 
         while matches!(self.current, Tok::Plus | Tok::Minus) {
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.factor_op();
+            let right = self.factor_op()?;
 
-            left = Op::Binary(Box::new(left), operator, Box::new(right))
+            left = self.binary(left, operator, right)
         }
 
-        left
+        Ok(left)
     }
 
-    fn and_op(&mut self) -> Op {
-        let mut left = self.term_op();
+    fn and_op(&mut self) -> Result<Op> {
+        let mut left = self.term_op()?;
 
         while matches!(self.current, Tok::And) {
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.factor_op();
+            let right = self.factor_op()?;
 
-            left = Op::Binary(Box::new(left), operator, Box::new(right))
+            left = self.binary(left, operator, right)
         }
 
-        left
+        Ok(left)
     }
 
-    fn or_op(&mut self) -> Op {
-        let mut left = self.and_op();
+    fn or_op(&mut self) -> Result<Op> {
+        let mut left = self.and_op()?;
 
         while matches!(self.current, Tok::Or) {
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.and_op();
+            let right = self.and_op()?;
 
-            left = Op::Binary(Box::new(left), operator, Box::new(right))
+            left = self.binary(left, operator, right)
         }
 
-        left
+        Ok(left)
     }
 
-    fn comparison_op(&mut self) -> Op {
-        let mut left = self.or_op();
+    fn comparison_op(&mut self) -> Result<Op> {
+        let mut left = self.or_op()?;
 
         while matches!(self.current, Tok::Gt | Tok::GtOrEq | Tok::Lt | Tok::LtOrEq) {
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.or_op();
+            let right = self.or_op()?;
 
-            left = Op::Binary(Box::new(left), operator, Box::new(right))
+            left = self.binary(left, operator, right)
         }
-        left
+        Ok(left)
     }
 
-    fn equality_op(&mut self) -> Op {
-        let mut left = self.comparison_op();
+    fn equality_op(&mut self) -> Result<Op> {
+        let mut left = self.comparison_op()?;
 
         while matches!(self.current, Tok::Comp | Tok::Different) {
             let operator = self.current.clone();
             self.next_skip();
-            let right = self.term_op();
+            let right = self.term_op()?;
 
-            left = Op::Binary(Box::new(left), operator, Box::new(right))
+            left = self.binary(left, operator, right)
         }
-        left
+        Ok(left)
     }
 }
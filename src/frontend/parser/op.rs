@@ -1,10 +1,12 @@
 use super::super::tokens::Tokens as Tok;
+use super::super::Span;
 use super::literal::Literal;
-use super::statement::Statement;
+use super::statement::{Param, Statement};
+use super::types::Type;
 
 /// Operations Enum, you can think of it as `Expr` in most parsers
 #[derive(Debug, Clone)]
-pub enum Op {
+pub enum OpKind {
     Primary(Box<Literal>),
     Call(Box<Op>, Vec<Op>),
     Unary(Tok, Box<Literal>),
@@ -12,4 +14,30 @@ pub enum Op {
     Grouping(Box<Op>),
     Block(Vec<Statement>),
     If(Box<Op>, Box<Op>, Box<Op>),
+    Access(Box<Op>, String), // `module.name`
+    Lambda(Vec<Param>, Option<Type>, Box<Statement>), // the body is always an `Op` statement
+    List(Vec<Op>),
+    Map(Vec<(String, Op)>),
+    Template(Vec<Op>), // string with `${...}`, the values of its parts are joined
+    Index(Box<Op>, Box<Op>),
+    Slice(Box<Op>, Option<Box<Op>>, Option<Box<Op>>), // `list[start:end]`, both bounds are optional
+    While(Box<Op>, Box<Op>),
+    For(String, Box<Op>, Box<Op>), // `for name in iterable do ... end`
+    Break,
+    Continue,
+    Return(Option<Box<Op>>), // `return` without a value returns `none`
+    Error, // code that failed to parse
+}
+
+/// An operation and the source code it was parsed from
+#[derive(Debug, Clone)]
+pub struct Op {
+    pub kind: OpKind,
+    pub span: Span,
+}
+
+impl Op {
+    pub fn new(kind: OpKind, span: Span) -> Self {
+        Self { kind, span }
+    }
 }
@@ -1,7 +1,32 @@
-use super::Op;
+use super::super::Span;
+use super::{Op, Type};
+
+/// A function parameter and its optional type annotation
 #[derive(Debug, Clone)]
-pub enum Statement {
-    Assign(String, Box<Op>),
-    FuncAssign(String, Vec<String>, Box<Statement>),
+pub struct Param {
+    pub name: String,
+    pub annotation: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum StatementKind {
+    Assign(String, Option<Type>, Box<Op>),
+    Reassign(String, Box<Op>), // `x = value`, compound assignments like `x += 1` are `x = x + 1`
+    FuncAssign(String, Vec<Param>, Option<Type>, Box<Statement>),
+    Import(String, Vec<String>), // path and the names to import, with none the module is bound to its file name
+    Export(Box<Statement>),
     Op(Op),
 }
+
+/// A statement and the source code it was parsed from
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
@@ -0,0 +1,71 @@
+use std::fmt;
+
+/// Types used by annotations and by the type checker
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Num,
+    Int, // also accepted where a `Num` is expected
+    BigInt,
+    Decimal,
+    Str,
+    Bool,
+    None,
+    List,
+    Map,
+    Function(Vec<Type>, Box<Type>),
+    Any, // unknown type, accepts and is accepted by every other type
+    Never, // operations that jump away instead of having a value, like `return`
+}
+
+impl Type {
+    /// Check if a value of type `other` can be used where `self` is expected
+    pub fn accepts(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Any, _) | (_, Type::Any) | (_, Type::Never) => true,
+            (Type::Num, Type::Int) => true,
+            (Type::Function(s_params, s_ret), Type::Function(o_params, o_ret)) => {
+                s_params.len() == o_params.len()
+                    && s_params.iter().zip(o_params).all(|(s, o)| s.accepts(o))
+                    && s_ret.accepts(o_ret)
+            }
+            _ => self == other,
+        }
+    }
+
+    /// Whether values of this type can be used with arithmetic operators
+    pub fn is_number(&self) -> bool {
+        Type::Num.accepts(self) || matches!(self, Type::BigInt | Type::Decimal)
+    }
+
+    /// The type of a value that can be either `self` or `other`
+    pub fn join(&self, other: &Type) -> Type {
+        match (self, other) {
+            (Type::Never, ty) | (ty, Type::Never) => ty.clone(),
+            (Type::Num, Type::Int) | (Type::Int, Type::Num) => Type::Num,
+            _ if self == other => self.clone(),
+            _ => Type::Any,
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Num => write!(f, "Num"),
+            Type::Int => write!(f, "Int"),
+            Type::BigInt => write!(f, "BigInt"),
+            Type::Decimal => write!(f, "Decimal"),
+            Type::Str => write!(f, "Str"),
+            Type::Bool => write!(f, "Bool"),
+            Type::None => write!(f, "None"),
+            Type::List => write!(f, "List"),
+            Type::Map => write!(f, "Map"),
+            Type::Any => write!(f, "Any"),
+            Type::Never => write!(f, "Never"),
+            Type::Function(params, ret) => {
+                let params: Vec<String> = params.iter().map(|p| p.to_string()).collect();
+                write!(f, "({}) -> {}", params.join(", "), ret)
+            }
+        }
+    }
+}
@@ -1,3 +1,21 @@
+use super::Span;
+use bigdecimal::BigDecimal;
+use num_bigint::BigInt;
+
+/// A token together with where it was found in the source
+#[derive(Debug, PartialEq, Clone)]
+pub struct Token {
+    pub tok: Tokens,
+    pub span: Span,
+}
+
+/// A piece of a string with `${...}`: its text, or the tokens of the code inside of the braces
+#[derive(Debug, PartialEq, Clone)]
+pub enum TemplatePart {
+    Str(String),
+    Code(Vec<Token>),
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum Tokens {
     // reserved fields
@@ -6,8 +24,12 @@ pub enum Tokens {
 
     // value fields
     Ident(String), // identifier, like: let main = 1
-    Num(f64),
+    Num(f64), // number with a `.`, like 1.5
+    Int(i64),
+    BigInt(BigInt),      // integer with an `n` suffix, like 100n
+    Decimal(BigDecimal), // exact decimal with a `d` suffix, like 19.99d
     Str(String),
+    Template(Vec<TemplatePart>), // string with `${...}` in it
 
     // reserved keywords
     True,
@@ -24,6 +46,16 @@ pub enum Tokens {
     Or,
     Not,
     And,
+    Import,
+    Export,
+    From,
+    Fn,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Return,
 
     // Symbols
     Semicolon, // `;`
@@ -31,13 +63,20 @@ pub enum Tokens {
     Plus,
     Asterisk, // `*`
     Slash,    // `/`
+    SlashSlash, // `//`, integer division
     Percent,  // `%`
     Pow,      // `**`
     Rparen,
     Lparen,
     Rbrace,
     Lbrace,
+    Rbracket, // `]`
+    Lbracket, // `[`
     Assign,      // `=`
+    PlusAssign,     // `+=`
+    MinusAssign,    // `-=`
+    AsteriskAssign, // `*=`
+    SlashAssign,    // `/=`
     Comp,        // `==`
     Different,   // `~=` or `!=` in other languages
     Gt,          // `>`
@@ -46,6 +85,9 @@ pub enum Tokens {
     LtOrEq,      // `<=`
     Comma,       // `,`
     Point,       // `.`
+    Colon,       // `:`
+    Arrow,       // `->`
+    FatArrow,    // `=>`
 }
 
 impl std::fmt::Display for Tokens {
@@ -65,27 +107,41 @@ impl std::fmt::Display for Tokens {
                 Comp => s("=="),
                 Different => s("~="),
                 Assign => s("="),
+                PlusAssign => s("+="),
+                MinusAssign => s("-="),
+                AsteriskAssign => s("*="),
+                SlashAssign => s("/="),
 
                 Lparen => s("("),
                 Rparen => s(")"),
                 Lbrace => s("{"),
                 Rbrace => s("}"),
+                Lbracket => s("["),
+                Rbracket => s("]"),
 
                 Comma => s(","),
                 Newline => s("newline"),
                 Point => s("."),
+                Colon => s(":"),
+                Arrow => s("->"),
+                FatArrow => s("=>"),
                 Semicolon => s(";"),
 
                 Minus => s("-"),
                 Plus => s("+"),
                 Asterisk => s("*"),
                 Slash => s("/"),
+                SlashSlash => s("//"),
                 Percent => s("%"),
                 Pow => s("**"),
 
                 Ident(ref l) => l.to_string(),
                 Num(ref f) => f.to_string(),
+                Int(ref n) => n.to_string(),
+                BigInt(ref n) => format!("{}n", n),
+                Decimal(ref n) => format!("{}d", n),
                 Str(ref s) => s.to_string(),
+                Template(..) => s("string"),
 
                 True => s("true"),
                 False => s("false"),
@@ -104,6 +160,18 @@ impl std::fmt::Display for Tokens {
                 Or => s("or"),
                 And => s("and"),
                 Not => s("not"),
+
+                Import => s("import"),
+                Export => s("export"),
+                From => s("from"),
+                Fn => s("fn"),
+
+                While => s("while"),
+                For => s("for"),
+                In => s("in"),
+                Break => s("break"),
+                Continue => s("continue"),
+                Return => s("return"),
             }
         )
     }
@@ -129,6 +197,9 @@ impl Tokens {
         matches!(
             *self,
                 Self::Num(..)
+                | Self::Int(..)
+                | Self::BigInt(..)
+                | Self::Decimal(..)
                 | Self::Str(..)
                 | Self::True
                 | Self::False
@@ -141,6 +212,13 @@ impl Tokens {
         matches!(*self, Self::Not | Self::Minus)
     }
 
+    pub fn is_assignment(&self) -> bool {
+        matches!(
+            *self,
+            Self::Assign | Self::PlusAssign | Self::MinusAssign | Self::AsteriskAssign | Self::SlashAssign
+        )
+    }
+
     pub fn is_ident(&self) -> bool {
         matches!(*self, Self::Ident(_))
     }
@@ -165,6 +243,16 @@ pub fn keyword_get_tok(k: &str) -> Option<Tokens> {
         "none" => Some(Tokens::None),
         "and" => Some(Tokens::And),
         "or" => Some(Tokens::Or),
+        "import" => Some(Tokens::Import),
+        "export" => Some(Tokens::Export),
+        "from" => Some(Tokens::From),
+        "fn" => Some(Tokens::Fn),
+        "while" => Some(Tokens::While),
+        "for" => Some(Tokens::For),
+        "in" => Some(Tokens::In),
+        "break" => Some(Tokens::Break),
+        "continue" => Some(Tokens::Continue),
+        "return" => Some(Tokens::Return),
         "end" => Some(Tokens::End), // the same as `;;`
         _ => None,
     }
@@ -0,0 +1,185 @@
+use super::builtins::native;
+use super::primitive::{to_big_int, to_decimal};
+use super::{Arity, Context, Primitive};
+use crate::error::Result;
+use bigdecimal::{BigDecimal, FromPrimitive, One, RoundingMode, Zero};
+use num_bigint::{BigInt, Sign};
+use std::{collections::HashMap, convert::TryFrom, num::NonZeroU64};
+
+/// The precision and rounding mode of the decimals of the running program
+fn decimals() -> (NonZeroU64, RoundingMode) {
+    Context::with(|context| context.decimals.get())
+}
+
+/// The names of the rounding modes, as `decimal_rounding` takes them
+const ROUNDINGS: [(&str, RoundingMode); 7] = [
+    ("half_even", RoundingMode::HalfEven),
+    ("half_up", RoundingMode::HalfUp),
+    ("half_down", RoundingMode::HalfDown),
+    ("up", RoundingMode::Up),
+    ("down", RoundingMode::Down),
+    ("ceiling", RoundingMode::Ceiling),
+    ("floor", RoundingMode::Floor),
+];
+
+/// Round a decimal with more significant digits than the precision allows
+pub fn rounded(value: BigDecimal) -> BigDecimal {
+    let (precision, rounding) = decimals();
+    match value.digits() > precision.get() {
+        true => value.with_precision_round(precision, rounding),
+        false => value,
+    }
+}
+
+/// `a / b` rounded to the precision, `b` can't be zero. Exact quotients keep the digits of `a`
+/// that aren't zeros, so `10.00d / 2` is `5.00`
+pub fn divide(a: &BigDecimal, b: &BigDecimal) -> BigDecimal {
+    let (precision, rounding) = decimals();
+    let (a_digits, a_scale) = a.as_bigint_and_exponent();
+    let (b_digits, b_scale) = b.as_bigint_and_exponent();
+
+    // shift `a` so the quotient has at least one digit more than the precision
+    let shift = (precision.get() as i64 + b.digits() as i64 - a.digits() as i64 + 1).max(0);
+    let numerator = a_digits * BigInt::from(10).pow(shift as u32);
+    let (quotient, remainder) = (&numerator / &b_digits, &numerator % &b_digits);
+    let scale = a_scale - b_scale + shift;
+
+    if remainder.is_zero() {
+        let ideal = a_scale - b_scale;
+        let exact = BigDecimal::new(quotient, scale).normalized();
+        return rounded(match exact.fractional_digit_count() < ideal {
+            true => exact.with_scale(ideal),
+            false => exact,
+        });
+    }
+
+    // a last digit that isn't zero marks the digits left out, so rounding sees them
+    let sticky = match numerator.sign() == b_digits.sign() {
+        true => 1,
+        false => -1,
+    };
+    let quotient = BigDecimal::new(quotient * 10 + sticky, scale + 1);
+    quotient.with_precision_round(precision, rounding)
+}
+
+/// `base ** exponent` with the precision of the decimals, `base` can't be zero when `exponent` is
+/// negative
+pub fn power(base: &BigDecimal, exponent: i64) -> BigDecimal {
+    let mut result = BigDecimal::one();
+    let mut square = base.clone();
+    let mut remaining = exponent.unsigned_abs();
+    while remaining > 0 {
+        if remaining & 1 == 1 {
+            result = rounded(result * &square);
+        }
+        square = rounded(&square * &square);
+        remaining >>= 1;
+    }
+
+    match exponent < 0 {
+        true => divide(&BigDecimal::one(), &result),
+        false => result,
+    }
+}
+
+/// Quotient rounded down and remainder with the sign of `b` of integers, `b` can't be zero
+pub fn floor_div_rem(a: &BigInt, b: &BigInt) -> (BigInt, BigInt) {
+    let (quotient, remainder) = (a / b, a % b);
+    if remainder.sign() != Sign::NoSign && remainder.sign() != b.sign() {
+        return (quotient - 1, remainder + b);
+    }
+    (quotient, remainder)
+}
+
+/// Quotient rounded down and remainder of decimals, computed exactly. `b` can't be zero
+pub fn decimal_floor_div_rem(a: &BigDecimal, b: &BigDecimal) -> (BigDecimal, BigDecimal) {
+    // with the same scale, the digits divide like integers
+    let scale = a.fractional_digit_count().max(b.fractional_digit_count());
+    let (a_digits, _) = a.with_scale(scale).into_bigint_and_exponent();
+    let (b_digits, _) = b.with_scale(scale).into_bigint_and_exponent();
+    let (quotient, remainder) = floor_div_rem(&a_digits, &b_digits);
+    (BigDecimal::from(quotient), BigDecimal::new(remainder, scale))
+}
+
+/// The whole number of digits `name` expects
+fn places(name: &str, value: &Primitive) -> Result<i64> {
+    match value {
+        Primitive::Int(n) => Ok(*n),
+        e => Err(crate::error!(Type; "`{}` expects an integer number of digits, found {}", name, e)),
+    }
+}
+
+/// Add the big integer and decimal natives to the builtins
+pub fn bignums(current: &mut HashMap<String, Primitive>) {
+    // decimals and floats are truncated towards zero
+    native(current, "bigint", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::Decimal(n) => Ok(Primitive::big_int(
+            n.with_scale_round(0, RoundingMode::Down).into_bigint_and_exponent().0,
+        )),
+        Primitive::Num(n) => match BigInt::from_f64(n.trunc()) {
+            Some(n) => Ok(Primitive::big_int(n)),
+            None => Err(crate::error!(Runtime; "can't convert {} to a big integer", n)),
+        },
+        Primitive::Str(s) => match s.trim().parse() {
+            Ok(n) => Ok(Primitive::big_int(n)),
+            Err(_) => Err(crate::error!(Runtime; "can't convert {:?} to a big integer", s)),
+        },
+        value => match to_big_int(value) {
+            Some(n) => Ok(Primitive::big_int(n)),
+            None => Err(crate::error!(Type; "can't convert {} to a big integer", value)),
+        },
+    });
+
+    // floats are converted from the digits they're shown with, so `decimal(0.1)` is `0.1d`
+    native(current, "decimal", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::Num(n) if n.is_finite() => Ok(Primitive::decimal(n.to_string().parse().unwrap())),
+        Primitive::Num(n) => Err(crate::error!(Runtime; "can't convert {} to a decimal", n)),
+        Primitive::Str(s) => match s.trim().parse() {
+            Ok(n) => Ok(Primitive::decimal(n)),
+            Err(_) => Err(crate::error!(Runtime; "can't convert {:?} to a decimal", s)),
+        },
+        value => match to_decimal(value) {
+            Some(n) => Ok(Primitive::decimal(n)),
+            None => Err(crate::error!(Type; "can't convert {} to a decimal", value)),
+        },
+    });
+
+    native(current, "decimal_precision", Arity::Fixed(1), |arguments| {
+        let digits = places("decimal_precision", &arguments[0])?;
+        match u64::try_from(digits).ok().and_then(NonZeroU64::new) {
+            Some(digits) => Context::with(|context| context.decimals.set((digits, context.decimals.get().1))),
+            None => {
+                return Err(crate::error!(Runtime; "decimals need a precision of at least one digit, found {}", digits))
+            }
+        }
+        Ok(Primitive::None)
+    });
+
+    native(current, "decimal_rounding", Arity::Fixed(1), |arguments| {
+        let rounding = ROUNDINGS.iter().find(|(name, _)| match &arguments[0] {
+            Primitive::Str(s) => s == name,
+            _ => false,
+        });
+        match rounding {
+            Some((_, rounding)) => Context::with(|context| context.decimals.set((context.decimals.get().0, *rounding))),
+            None => {
+                let names: Vec<&str> = ROUNDINGS.iter().map(|(name, _)| *name).collect();
+                return Err(
+                    crate::error!(Runtime; "unknown rounding mode {}, expected one of {}", arguments[0], names.join(", ")),
+                );
+            }
+        }
+        Ok(Primitive::None)
+    });
+
+    // rounds with the rounding mode of the decimals, to a number of digits after the `.`
+    native(current, "round_to", Arity::Fixed(2), |arguments| {
+        let value = match to_decimal(&arguments[0]) {
+            Some(value) => value,
+            None => return Err(crate::error!(Type; "`round_to` expects an exact number, found {}", arguments[0])),
+        };
+        let places = places("round_to", &arguments[1])?;
+        let (_, rounding) = decimals();
+        Ok(Primitive::decimal(value.with_scale_round(places, rounding)))
+    });
+}
@@ -0,0 +1,199 @@
+use super::{bignum::bignums, math::math, strings::strings, Arity, FromPrimitive, NativeFunction, Primitive};
+use crate::error::Result;
+use std::{
+    cell::RefCell,
+    cmp,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+};
+use std::io::{stdout, Write};
+
+/// Add a native function to the builtins
+pub(super) fn native(
+    current: &mut HashMap<String, Primitive>,
+    name: &str,
+    arity: Arity,
+    func: impl Fn(&[Primitive]) -> Result<Primitive> + 'static,
+) {
+    let function = NativeFunction::new(name, arity, func);
+    current.insert(name.to_string(), Primitive::NativeFunc(Rc::new(function)));
+}
+
+/// The arguments of `write` and `writeln`, separated by spaces
+fn joined(arguments: &[Primitive]) -> String {
+    let items: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+    items.join(" ")
+}
+
+/// The largest or smallest of the numbers `name` got, whichever `keep` chooses from the ordering
+/// of the next number against the current one. Integers stay integers
+pub(super) fn extreme(name: &str, arguments: &[Primitive], keep: fn(cmp::Ordering) -> bool) -> Result<Primitive> {
+    let mut result: Option<&Primitive> = None;
+    for argument in arguments {
+        match argument {
+            Primitive::Num(_) | Primitive::Int(_) | Primitive::BigInt(_) | Primitive::Decimal(_) => {
+                let better = match result {
+                    Some(current) => argument.compare(current)?.is_some_and(keep),
+                    None => true,
+                };
+                if better {
+                    result = Some(argument)
+                }
+            }
+            e => return Err(crate::error!(Type; "`{}` expects numbers, found {}", name, e)),
+        }
+    }
+    Ok(result.cloned().unwrap_or(Primitive::None))
+}
+
+/// The most items `range` makes
+const MAX_RANGE: u64 = 10_000_000;
+
+/// The largest float up to which every integer is a float too, 2^53
+const MAX_EXACT_FLOAT: f64 = 9_007_199_254_740_992.0;
+
+/// The number of items of a range that takes `steps` steps, which can't be more than `MAX_RANGE`
+fn range_length(steps: f64) -> Result<u64> {
+    match steps {
+        steps if steps > MAX_RANGE as f64 => Err(crate::error!(Runtime;
+            "a range of {} items is too big, `range` makes at most {} items", steps, MAX_RANGE
+        )),
+        steps if steps > 0.0 => Ok(steps as u64),
+        _ => Ok(0),
+    }
+}
+
+/// The map and the key the map natives got as their first two arguments
+fn map_key<'a>(name: &str, arguments: &'a [Primitive]) -> Result<(&'a RefCell<BTreeMap<String, Primitive>>, &'a str)> {
+    match (&arguments[0], &arguments[1]) {
+        (Primitive::Map(map), Primitive::Str(key)) => Ok((map, key)),
+        (Primitive::Map(_), key) => Err(crate::error!(Type; "map keys must be strings, found {}", key)),
+        (e, _) => Err(crate::error!(Type; "`{}` expects a map, found {}", name, e)),
+    }
+}
+
+/// Native functions available in every program, shared by the tree-walker and the vm
+pub fn builtins() -> HashMap<String, Primitive> {
+    let mut current = HashMap::new();
+    native(&mut current, "writeln", Arity::Variadic(0), |arguments| {
+        println!("{}", joined(arguments));
+        stdout().flush().expect("Error writing line");
+        Ok(Primitive::None)
+    });
+
+    native(&mut current, "write", Arity::Variadic(0), |arguments| {
+        print!("{}", joined(arguments));
+        stdout().flush().expect("Error writing line");
+        Ok(Primitive::None)
+    });
+
+    native(&mut current, "__print_typeof", Arity::Fixed(1), |arguments| {
+        println!("{:?}", arguments[0]);
+        Ok(Primitive::None)
+    });
+
+    native(&mut current, "typeof", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Str(arguments[0].type_name().to_string()))
+    });
+
+    native(&mut current, "len", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::List(list) => Ok(Primitive::Int(list.borrow().len() as i64)),
+        Primitive::Map(map) => Ok(Primitive::Int(map.borrow().len() as i64)),
+        Primitive::Str(s) => Ok(Primitive::Int(s.chars().count() as i64)),
+        e => Err(crate::error!(Type; "can't get the length of {}", e)),
+    });
+
+    native(&mut current, "range", Arity::Range(1, 2), |arguments| {
+        // `range(end)` starts at zero, `range(start, end)` doesn't include `end`
+        let (start, end) = match arguments {
+            [end] => (&Primitive::Int(0), end),
+            [start, end] => (start, end),
+            _ => unreachable!(),
+        };
+
+        // the list is made all at once, so its length is counted first
+        let items: Vec<Primitive> = match (start, end) {
+            (Primitive::Int(start), Primitive::Int(end)) => {
+                range_length(*end as f64 - *start as f64)?;
+                (*start..*end).map(Primitive::Int).collect()
+            }
+            (start, end) => {
+                let (start, end) = match (f64::from_pri(start.clone()), f64::from_pri(end.clone())) {
+                    (Ok(start), Ok(end)) => (start, end),
+                    _ => return Err(crate::error!(Type; "`range` expects numbers")),
+                };
+                // past 2^53 adding one to a float can leave it the same
+                if start.abs().max(end.abs()) > MAX_EXACT_FLOAT {
+                    return Err(
+                        crate::error!(Runtime; "`range` can't count with floats past {}, use integers", MAX_EXACT_FLOAT),
+                    );
+                }
+                (0..range_length((end - start).ceil())?)
+                    .map(|step| Primitive::Num(start + step as f64))
+                    .collect()
+            }
+        };
+        Ok(Primitive::list(items))
+    });
+
+    native(&mut current, "push", Arity::Fixed(2), |arguments| {
+        match &arguments[0] {
+            Primitive::List(list) => list.borrow_mut().push(arguments[1].clone()),
+            e => return Err(crate::error!(Type; "can't push to {}", e)),
+        }
+        Ok(Primitive::None)
+    });
+
+    native(&mut current, "pop", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::List(list) => list
+            .borrow_mut()
+            .pop()
+            .ok_or_else(|| crate::error!(Runtime; "can't pop from an empty list")),
+        e => Err(crate::error!(Type; "can't pop from {}", e)),
+    });
+
+    native(&mut current, "insert", Arity::Fixed(3), |arguments| {
+        let (map, key) = map_key("insert", arguments)?;
+        map.borrow_mut().insert(key.to_string(), arguments[2].clone());
+        Ok(Primitive::None)
+    });
+
+    native(&mut current, "remove", Arity::Fixed(2), |arguments| {
+        let (map, key) = map_key("remove", arguments)?;
+        let removed = map.borrow_mut().remove(key);
+        removed.ok_or_else(|| crate::error!(Reference; "map has no key {:?}", key))
+    });
+
+    native(&mut current, "has", Arity::Fixed(2), |arguments| {
+        let (map, key) = map_key("has", arguments)?;
+        let found = map.borrow().contains_key(key);
+        Ok(Primitive::Bool(found))
+    });
+
+    native(&mut current, "keys", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::Map(map) => Ok(Primitive::list(
+            map.borrow().keys().map(|key| Primitive::Str(key.to_string())).collect(),
+        )),
+        e => Err(crate::error!(Type; "`keys` expects a map, found {}", e)),
+    });
+
+    native(&mut current, "values", Arity::Fixed(1), |arguments| {
+        match &arguments[0] {
+            Primitive::Map(map) => Ok(Primitive::list(map.borrow().values().cloned().collect())),
+            e => Err(crate::error!(Type; "`values` expects a map, found {}", e)),
+        }
+    });
+
+    native(&mut current, "max", Arity::Variadic(1), |arguments| {
+        extreme("max", arguments, |order| order == cmp::Ordering::Greater)
+    });
+    native(&mut current, "min", Arity::Variadic(1), |arguments| {
+        extreme("min", arguments, |order| order == cmp::Ordering::Less)
+    });
+
+    strings(&mut current);
+    bignums(&mut current);
+    current.insert("math".to_string(), math());
+
+    current
+}
@@ -0,0 +1,58 @@
+use bigdecimal::RoundingMode;
+use std::{
+    cell::{Cell, RefCell},
+    num::NonZeroU64,
+    rc::Rc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+thread_local! {
+    // the context of the runtime running on this thread, when one is running
+    static CURRENT: RefCell<Option<Rc<Context>>> = const { RefCell::new(None) };
+}
+
+/// State of a runtime that natives and operators use without getting it as an argument, like the
+/// random numbers of the math module and how decimals are rounded. Each runtime has its own,
+/// shared by every module of the program it runs
+pub struct Context {
+    pub random: Cell<u64>, // state of the random numbers
+    // significant digits kept by decimal operations and how they're rounded, like Python's decimal
+    pub decimals: Cell<(NonZeroU64, RoundingMode)>,
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        // without a seed the random numbers change on every run
+        let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64);
+        Self {
+            random: Cell::new(time),
+            decimals: Cell::new((NonZeroU64::new(28).unwrap(), RoundingMode::HalfEven)),
+        }
+    }
+}
+
+/// Puts back the context that was running before a runtime started, even when it panics
+struct Entered(Option<Rc<Context>>);
+
+impl Drop for Entered {
+    fn drop(&mut self) {
+        let outer = self.0.take();
+        CURRENT.with(|current| *current.borrow_mut() = outer);
+    }
+}
+
+impl Context {
+    /// Run `run` with this context as the context of the running runtime
+    pub fn enter<T>(self: &Rc<Self>, run: impl FnOnce() -> T) -> T {
+        let _entered = Entered(CURRENT.with(|current| current.replace(Some(self.clone()))));
+        run()
+    }
+
+    /// Use the context of the running runtime. Outside of a runtime a new context is used
+    pub fn with<T>(use_context: impl FnOnce(&Context) -> T) -> T {
+        match CURRENT.with(|current| current.borrow().clone()) {
+            Some(context) => use_context(&context),
+            None => use_context(&Context::default()),
+        }
+    }
+}
@@ -1,28 +1,53 @@
-use super::{Env, Interpreter};
+use super::{CallStack, Context, Env, Interpreter, DEFAULT_MAX_DEPTH};
+use crate::error::Result;
 use crate::frontend::Statement;
+use crate::modules::ModuleLoader;
+use std::{cell::RefCell, rc::Rc};
 
-pub struct InterpreterDebug<'a> {
-    variables: Env<'a>,
+pub struct InterpreterDebug {
+    variables: Env,
+    modules: Rc<RefCell<ModuleLoader>>,
+    calls: Rc<RefCell<CallStack>>,
+    context: Rc<Context>,
 }
 
-impl<'a> InterpreterDebug<'a> {
-    pub fn interpret_debug(&mut self, mut operations: impl Iterator<Item = Statement>) {
+impl InterpreterDebug {
+    /// Interpreter that allows at most `max_depth` nolang calls running at once
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            variables: Env::default(),
+            modules: Default::default(),
+            calls: CallStack::new(max_depth),
+            context: Default::default(),
+        }
+    }
+
+    /// Interpret the statements, printing the result of each one. Variables defined before an
+    /// error are kept
+    pub fn interpret_debug(&mut self, operations: impl Iterator<Item = Statement>) -> Result<()> {
         let mut runtime = Interpreter {
             variables: self.variables.clone(),
+            modules: self.modules.clone(),
+            calls: self.calls.clone(),
         };
 
-        while let Some(op) = operations.next() {
-            println!("=> {}", runtime.statement(op));
-        }
+        let result = self.context.enter(|| {
+            for op in operations {
+                match runtime.statement(op) {
+                    Ok(value) => println!("=> {}", value),
+                    Err(e) => return Err(e.into_error()),
+                }
+            }
+            Ok(())
+        });
 
-        self.variables = runtime.variables
+        self.variables = runtime.variables;
+        result
     }
 }
 
-impl<'a> Default for InterpreterDebug<'a> {
+impl Default for InterpreterDebug {
     fn default() -> Self {
-        let variables = Env::default();
-
-        Self { variables }
+        Self::with_max_depth(DEFAULT_MAX_DEPTH)
     }
 }
@@ -0,0 +1,184 @@
+use super::{
+    Arity, CallStack, Context, Env, FromPrimitive, IntoPrimitive, Interpreter, NativeFunction, Primitive, Unwind,
+    DEFAULT_MAX_DEPTH,
+};
+use crate::checker::Checker;
+use crate::error::{NolangError, Result};
+use crate::frontend::{parse, Span, Statement};
+use crate::modules::ModuleLoader;
+use std::{cell::RefCell, rc::Rc};
+
+/// Runs nolang code inside of a Rust program. The host can define globals and functions that
+/// the code uses, and read back the values it defines
+///
+/// ```
+/// use nolang::{Engine, IntoPrimitive};
+///
+/// let mut engine = Engine::new();
+/// engine.register_fn("double", |x: f64| x * 2.0);
+/// engine.set_global("limit", 10.0);
+///
+/// engine.eval::<()>("defn(x) over = double(x) > limit").unwrap();
+/// assert!(engine.call_function::<bool>("over", vec![6.0.into_pri()]).unwrap());
+/// assert_eq!(engine.eval::<f64>("double(limit)").unwrap(), 20.0);
+/// ```
+pub struct Engine {
+    variables: Env,
+    modules: Rc<RefCell<ModuleLoader>>,
+    calls: Rc<RefCell<CallStack>>,
+    checker: Checker,
+    context: Rc<Context>,
+}
+
+impl Engine {
+    /// Engine that allows `DEFAULT_MAX_DEPTH` nolang calls running at once. The native stack
+    /// grows as the calls need it, so the engine can run on any thread
+    pub fn new() -> Self {
+        Self::with_max_depth(DEFAULT_MAX_DEPTH)
+    }
+
+    /// Engine that allows at most `max_depth` nolang calls running at once
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            variables: Env::default(),
+            modules: Default::default(),
+            calls: CallStack::new(max_depth),
+            checker: Checker::default(),
+            context: Default::default(),
+        }
+    }
+
+    /// Make a Rust function callable from nolang. Its arguments are converted with
+    /// `FromPrimitive`, and its result with `IntoPrimitive`
+    pub fn register_fn<Args>(&mut self, name: &str, function: impl HostFunction<Args>) {
+        let native = function.into_native(name);
+        self.set_global(name, Primitive::NativeFunc(Rc::new(native)));
+    }
+
+    /// Define a global variable, replacing the one with the same name
+    pub fn set_global(&mut self, name: &str, value: impl IntoPrimitive) {
+        self.variables.set(name.to_string(), value.into_pri());
+        self.checker.declare(name);
+    }
+
+    /// Get a global variable, defined by the host or by the code that was run
+    pub fn get_global<T: FromPrimitive>(&self, name: &str) -> Result<T> {
+        T::from_pri(self.variables.get(name)?)
+    }
+
+    /// Parse, check and run `source`, returning the value of its last statement. The globals it
+    /// defines are kept for the next calls. When `source` has several errors, the first one is
+    /// returned with the others as its notes
+    pub fn eval<T: FromPrimitive>(&mut self, source: &str) -> Result<T> {
+        let program: Vec<Statement> = match parse(source.to_string(), "<eval>") {
+            Ok(program) => program.collect(),
+            Err(errors) => return Err(NolangError::from_many(errors)),
+        };
+        let errors = self.checker.check(&program);
+        if !errors.is_empty() {
+            return Err(NolangError::from_many(errors));
+        }
+
+        let mut runtime = self.runtime();
+        let current = self.context.enter(|| {
+            let mut current = Primitive::None;
+            for statement in program {
+                current = runtime.statement(statement).map_err(Unwind::into_error)?;
+            }
+            Ok(current)
+        });
+        T::from_pri(current?)
+    }
+
+    /// Call the global function `name`, written in nolang or registered by the host
+    pub fn call_function<T: FromPrimitive>(&mut self, name: &str, arguments: Vec<Primitive>) -> Result<T> {
+        let called = self.variables.get(name)?;
+        let span = Span {
+            file: "<engine>".into(),
+            ..Default::default()
+        };
+
+        let mut runtime = self.runtime();
+        let result = self.context.enter(|| runtime.call(called, arguments, false, &span));
+        T::from_pri(result.map_err(Unwind::into_error)?)
+    }
+
+    fn runtime(&self) -> Interpreter {
+        Interpreter {
+            variables: self.variables.clone(),
+            modules: self.modules.clone(),
+            calls: self.calls.clone(),
+        }
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Engine {
+    // the functions defined by the code that was run keep the globals alive, and the globals keep
+    // them alive, unless they are freed here
+    fn drop(&mut self) {
+        self.variables.release();
+    }
+}
+
+/// What a function registered in an `Engine` can return: a value, or a `Result` to raise a
+/// nolang error
+pub trait NativeResult {
+    fn into_result(self) -> Result<Primitive>;
+}
+
+impl<T: IntoPrimitive> NativeResult for T {
+    fn into_result(self) -> Result<Primitive> {
+        Ok(self.into_pri())
+    }
+}
+
+impl<T: IntoPrimitive> NativeResult for Result<T> {
+    fn into_result(self) -> Result<Primitive> {
+        self.map(IntoPrimitive::into_pri)
+    }
+}
+
+/// Rust closures that can be registered in an `Engine`, `Args` are the types of their arguments
+pub trait HostFunction<Args> {
+    fn into_native(self, name: &str) -> NativeFunction;
+}
+
+macro_rules! host_function {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg: FromPrimitive),*> HostFunction<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> R + 'static,
+            R: NativeResult,
+        {
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            fn into_native(self, name: &str) -> NativeFunction {
+                let count = <[&str]>::len(&[$(stringify!($arg)),*]);
+                let called = name.to_string();
+
+                NativeFunction::new(name, Arity::Fixed(count), move |arguments| {
+                    let mut arguments = arguments.iter().cloned().enumerate();
+                    $(
+                        let (position, value) = arguments.next().unwrap();
+                        let $arg = $arg::from_pri(value).map_err(|e| {
+                            e.with_note(&format!("in argument {} of `{}`", position + 1, called), None)
+                        })?;
+                    )*
+                    self($($arg),*).into_result()
+                })
+            }
+        }
+    };
+}
+
+host_function!();
+host_function!(A);
+host_function!(A, B);
+host_function!(A, B, C);
+host_function!(A, B, C, D);
+host_function!(A, B, C, D, E);
@@ -1,22 +1,29 @@
-use super::{Env, Interpreter, Primitive};
+use super::{Context, Env, Interpreter, Primitive, Unwind};
+use crate::error::Result;
 use crate::frontend::Statement;
-use std::collections::HashMap;
+use crate::modules::ModuleLoader;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
 
-/// Wrapper interpreter function
-pub fn interpret(mut operations: impl Iterator<Item = Statement>, vars: Option<&Env>) -> Primitive {
+/// Wrapper interpreter function, at most `max_depth` nolang calls can be running at once. The
+/// imported modules are type checked when `type_check` is set
+pub fn interpret(
+    operations: impl Iterator<Item = Statement>,
+    vars: Option<&Env>,
+    max_depth: usize,
+    type_check: bool,
+) -> Result<Primitive> {
     let mut runtime = match vars {
-        Some(v) => Interpreter {
-            variables: Env::new(HashMap::new(), Some(v)),
-        },
-        None => Interpreter {
-            variables: Env::default(),
-        },
+        Some(v) => Interpreter::new(Env::new(HashMap::new(), Some(v)), max_depth),
+        None => Interpreter::new(Env::default(), max_depth),
     };
+    runtime.modules = Rc::new(RefCell::new(ModuleLoader::new(type_check)));
 
-    let mut current = Primitive::None;
+    Rc::new(Context::default()).enter(|| {
+        let mut current = Primitive::None;
 
-    while let Some(op) = operations.next() {
-        current = runtime.statement(op);
-    }
-    current
+        for op in operations {
+            current = runtime.statement(op).map_err(Unwind::into_error)?;
+        }
+        Ok(current)
+    })
 }
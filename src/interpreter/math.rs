@@ -0,0 +1,148 @@
+use super::builtins::{extreme, native};
+use super::{Arity, Context, Primitive};
+use crate::error::Result;
+use crate::modules::Module;
+use bigdecimal::{RoundingMode, Signed};
+use std::{cmp::Ordering, collections::HashMap, f64::consts, rc::Rc};
+
+/// The next random number of the running program, made by splitmix64
+fn next_random() -> u64 {
+    Context::with(|context| {
+        let next = context.random.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        context.random.set(next);
+
+        let mut z = next;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    })
+}
+
+/// The number argument of `name`
+fn number(name: &str, value: &Primitive) -> Result<f64> {
+    match value {
+        Primitive::Num(n) => Ok(*n),
+        Primitive::Int(n) => Ok(*n as f64),
+        e @ (Primitive::BigInt(_) | Primitive::Decimal(_)) => Err(crate::error!(Type;
+            "`{}` works with floats and integers, convert {} with `num` first", name, e
+        )),
+        e => Err(crate::error!(Type; "`{}` expects a number, found {}", name, e)),
+    }
+}
+
+/// The whole number argument of `name`
+fn whole(name: &str, value: &Primitive) -> Result<i64> {
+    if let Primitive::Int(n) = value {
+        return Ok(*n);
+    }
+    match number(name, value)? {
+        n if n.fract() == 0.0 && n.is_finite() => Ok(n as i64),
+        n => Err(crate::error!(Type; "`{}` expects a whole number, found {}", name, n)),
+    }
+}
+
+/// Add a function of one number to the module
+fn unary(exports: &mut HashMap<String, Primitive>, name: &'static str, func: fn(f64) -> f64) {
+    native(exports, name, Arity::Fixed(1), move |arguments| {
+        Ok(Primitive::Num(func(number(name, &arguments[0])?)))
+    });
+}
+
+/// Add a function that rounds a number to a whole one with `mode`. Integers are whole already,
+/// and decimals stay decimals
+fn rounding(exports: &mut HashMap<String, Primitive>, name: &'static str, func: fn(f64) -> f64, mode: RoundingMode) {
+    native(exports, name, Arity::Fixed(1), move |arguments| match &arguments[0] {
+        value @ (Primitive::Int(_) | Primitive::BigInt(_)) => Ok(value.clone()),
+        Primitive::Decimal(n) => Ok(Primitive::decimal(n.with_scale_round(0, mode))),
+        value => Ok(Primitive::Num(func(number(name, value)?))),
+    });
+}
+
+/// The `math` module, available in every program without importing it
+pub fn math() -> Primitive {
+    let mut exports = HashMap::new();
+    exports.insert("pi".to_string(), Primitive::Num(consts::PI));
+    exports.insert("e".to_string(), Primitive::Num(consts::E));
+
+    native(&mut exports, "abs", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::Int(n) => match n.checked_abs() {
+            Some(n) => Ok(Primitive::Int(n)),
+            None => Err(crate::error!(Runtime; "integer overflow in the absolute value of {}", n)),
+        },
+        Primitive::BigInt(n) => Ok(Primitive::big_int(n.abs())),
+        Primitive::Decimal(n) => Ok(Primitive::decimal(n.abs())),
+        value => Ok(Primitive::Num(number("abs", value)?.abs())),
+    });
+    rounding(&mut exports, "floor", f64::floor, RoundingMode::Floor);
+    rounding(&mut exports, "ceil", f64::ceil, RoundingMode::Ceiling);
+    rounding(&mut exports, "round", f64::round, RoundingMode::HalfUp); // halfway values are rounded away from zero
+    unary(&mut exports, "sqrt", f64::sqrt);
+    unary(&mut exports, "exp", f64::exp);
+    unary(&mut exports, "ln", f64::ln);
+    unary(&mut exports, "log10", f64::log10);
+    unary(&mut exports, "sin", f64::sin);
+    unary(&mut exports, "cos", f64::cos);
+    unary(&mut exports, "tan", f64::tan);
+    unary(&mut exports, "asin", f64::asin);
+    unary(&mut exports, "acos", f64::acos);
+    unary(&mut exports, "atan", f64::atan);
+
+    native(&mut exports, "atan2", Arity::Fixed(2), |arguments| {
+        let (y, x) = (number("atan2", &arguments[0])?, number("atan2", &arguments[1])?);
+        Ok(Primitive::Num(y.atan2(x)))
+    });
+
+    native(&mut exports, "max", Arity::Variadic(1), |arguments| {
+        extreme("max", arguments, |order| order == Ordering::Greater)
+    });
+
+    native(&mut exports, "min", Arity::Variadic(1), |arguments| {
+        extreme("min", arguments, |order| order == Ordering::Less)
+    });
+
+    native(&mut exports, "clamp", Arity::Fixed(3), |arguments| {
+        let value = number("clamp", &arguments[0])?;
+        let (low, high) = (number("clamp", &arguments[1])?, number("clamp", &arguments[2])?);
+        if low > high {
+            return Err(crate::error!(Runtime; "`clamp` got a minimum of {} bigger than the maximum of {}", low, high));
+        }
+        Ok(Primitive::Num(value.max(low).min(high)))
+    });
+
+    native(&mut exports, "is_nan", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Bool(number("is_nan", &arguments[0])?.is_nan()))
+    });
+
+    native(&mut exports, "is_finite", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Bool(number("is_finite", &arguments[0])?.is_finite()))
+    });
+
+    // the same seed always gives the same numbers, without one they change on every run
+    native(&mut exports, "seed", Arity::Fixed(1), |arguments| {
+        let seed = whole("seed", &arguments[0])?;
+        Context::with(|context| context.random.set(seed as u64));
+        Ok(Primitive::None)
+    });
+
+    native(&mut exports, "random", Arity::Fixed(0), |_| {
+        Ok(Primitive::Num((next_random() >> 11) as f64 / (1u64 << 53) as f64))
+    });
+
+    native(&mut exports, "random_int", Arity::Fixed(2), |arguments| {
+        // both ends are included
+        let (low, high) = (whole("random_int", &arguments[0])?, whole("random_int", &arguments[1])?);
+        if low > high {
+            return Err(
+                crate::error!(Runtime; "`random_int` got a minimum of {} bigger than the maximum of {}", low, high),
+            );
+        }
+        let range = (high as i128 - low as i128 + 1) as u128;
+        let offset = (next_random() as u128 * range) >> 64;
+        Ok(Primitive::Int((low as i128 + offset as i128) as i64))
+    });
+
+    Primitive::Module(Rc::new(Module {
+        name: "math".to_string(),
+        exports,
+    }))
+}
@@ -1,114 +1,255 @@
-#[macro_use]
-mod primitive;
+mod bignum;
+mod builtins;
+mod context;
 mod debug;
+mod engine;
 mod interpret;
+mod math;
+mod native;
+mod primitive;
+mod strings;
 
+pub use builtins::builtins;
+pub(crate) use context::Context;
 pub use debug::InterpreterDebug;
+pub use engine::{Engine, HostFunction, NativeResult};
 pub use interpret::interpret;
-
-use primitive::{IntoPrimitive, Primitive};
-use std::collections::HashMap;
-use std::io::{stdout, Write};
-
-use crate::frontend::{Literal, Op, Statement, Tokens as Tok};
-
-#[derive(Debug, Clone)]
-pub struct Env<'a> {
+pub use native::{Arity, NativeFunction};
+pub use primitive::{FromPrimitive, Function, IntoPrimitive, Primitive};
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+    rc::Rc,
+};
+
+use crate::error::{NolangError, Result, TraceFrame};
+use crate::frontend::{Literal, Op, OpKind, Span, Statement, StatementKind, Tokens as Tok};
+use crate::modules::{exported_names, module_name, Import, Module, ModuleLoader};
+
+struct Scope {
     current: HashMap<String, Primitive>,
-    over: Option<&'a Env<'a>>,
+    over: Option<Env>,
 }
 
-impl<'a> Default for Env<'a> {
+/// A scope of variables. Cloning it shares the scope, so the functions that captured it see the
+/// variables defined in it later
+#[derive(Clone)]
+pub struct Env(Rc<RefCell<Scope>>);
+
+impl Default for Env {
     fn default() -> Self {
-        let mut current = HashMap::new();
-        current.insert(
-            "writeln".to_string(),
-            Primitive::NativeFunc(|arg: Primitive| {
-                println!("{}", arg);
-                stdout().flush().expect("Error writing line");
-                Primitive::None
-            }),
-        );
-
-        current.insert(
-            "write".to_string(),
-            Primitive::NativeFunc(|arg: Primitive| {
-                print!("{}", arg);
-                stdout().flush().expect("Error writing line");
-                Primitive::None
-            }),
-        );
-
-        current.insert(
-            "__print_typeof".to_string(),
-            Primitive::NativeFunc(|arg: Primitive| {
-                println!("{:?}", arg);
-                Primitive::None
-            }),
-        );
-
-        current.insert(
-            "typeof".to_string(),
-            Primitive::NativeFunc(|arg: Primitive| {
-                Primitive::Str(
-                    match arg {
-                        Primitive::NativeFunc(..) | Primitive::Function(..) => "Function",
-                        Primitive::None => "None",
-                        Primitive::Num(..) => "Num",
-                        Primitive::Bool(..) => "Bool",
-                        Primitive::Str(..) => "Str",
-                    }
-                    .to_string(),
-                )
-            }),
-        );
+        Self::new(builtins(), None)
+    }
+}
 
-        Self {
+impl fmt::Debug for Env {
+    // the scope can hold functions that captured it, so its contents aren't printed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<env>")
+    }
+}
+
+impl Env {
+    fn new(current: HashMap<String, Primitive>, over: Option<&Env>) -> Self {
+        Self(Rc::new(RefCell::new(Scope {
             current,
-            over: None,
+            over: over.cloned(),
+        })))
+    }
+
+    fn set(&self, name: String, value: Primitive) {
+        self.0.borrow_mut().current.insert(name, value);
+    }
+
+    /// Update the innermost variable called `name`, in whichever scope it was defined
+    fn assign(&self, name: &str, value: Primitive) -> Result<()> {
+        let mut scope = self.0.borrow_mut();
+        match scope.current.get_mut(name) {
+            Some(current) => {
+                *current = value;
+                Ok(())
+            }
+            None => match &scope.over {
+                Some(o) => o.assign(name, value),
+                None => Err(crate::error!(Reference; "assigning to undeclared variable {}", name)),
+            },
         }
     }
-}
 
-impl<'a> Env<'a> {
-    fn new(current: HashMap<String, Primitive>, over: Option<&'a Env<'a>>) -> Self {
-        Self { current, over }
+    /// The environment of a function call, binding the arguments inside of the environment the
+    /// function captured
+    fn call(captured: &Env, params: &[String], arguments: Vec<Primitive>) -> Result<Env> {
+        if arguments.len() < params.len() {
+            return Err(crate::error!(Call; "Missing arguments for function call"));
+        }
+        let env = params.iter().cloned().zip(arguments).collect::<HashMap<_, _>>();
+
+        Ok(Env::new(env, Some(captured)))
     }
 
-    fn set(&mut self, name: String, value: Primitive) {
-        self.current.insert(name, value);
+    /// Called by the code that made the scope once it's done with it. A function stored in the
+    /// scope it captured keeps the scope alive, and the scope keeps it alive. When nothing else
+    /// holds the scope or those functions, its variables are dropped to free them
+    fn release(&self) {
+        let scope = self.0.borrow();
+        let mut own = 0;
+        for value in scope.current.values() {
+            if let Primitive::Function(function) = value {
+                if Rc::ptr_eq(&function.env.0, &self.0) {
+                    if Rc::strong_count(function) > 1 {
+                        return;
+                    }
+                    own += 1;
+                }
+            }
+        }
+
+        if own > 0 && Rc::strong_count(&self.0) == own + 1 {
+            drop(scope);
+            self.0.borrow_mut().current.clear();
+        }
     }
 
-    fn get(&self, name: &str) -> Primitive {
-        match self.current.get(name) {
-            Some(p) => p.clone(),
-            None => match &self.over {
+    fn get(&self, name: &str) -> Result<Primitive> {
+        let scope = self.0.borrow();
+        match scope.current.get(name) {
+            Some(p) => Ok(p.clone()),
+            None => match &scope.over {
                 Some(o) => o.get(name),
-                None => {
-                    crate::error!("ReferenceError"; "acessing undefined variable {}", name => 1)
-                }
+                None => Err(crate::error!(Reference; "acessing undefined variable {}", name)),
             },
         }
     }
 }
 
+/// Why an evaluation stopped before producing its value: an error, a `break` or `continue` going
+/// up to the loop that runs the body, or a `return` going up to the function call
+enum Unwind {
+    Error(NolangError),
+    Break,
+    Continue,
+    Return(Box<Primitive>), // boxed, since the variant would make every result much bigger
+    TailCall(Box<(Rc<Function>, Env)>), // a call in tail position, run by the call it replaces
+}
+
+/// The result of evaluating an operation inside of the interpreter
+type Flow<T> = std::result::Result<T, Unwind>;
+
+impl From<NolangError> for Unwind {
+    fn from(error: NolangError) -> Self {
+        Self::Error(error)
+    }
+}
+
+impl Unwind {
+    fn or_at(self, span: &Span) -> Self {
+        match self {
+            Self::Error(error) => Self::Error(error.or_at(span)),
+            signal => signal,
+        }
+    }
+
+    /// The error that stopped the program. The parser only allows `break` and `continue` inside
+    /// of loops and `return` inside of functions, so they never get out of one
+    fn into_error(self) -> NolangError {
+        match self {
+            Self::Error(error) => error,
+            Self::Break => crate::error!(Runtime; "`break` outside of a loop"),
+            Self::Continue => crate::error!(Runtime; "`continue` outside of a loop"),
+            Self::Return(_) => crate::error!(Runtime; "`return` outside of a function"),
+            Self::TailCall(_) => crate::error!(Runtime; "tail call outside of a function"),
+        }
+    }
+}
+
+/// How many nolang calls can be running at once before a `StackOverflowError`, unless another
+/// limit is given
+pub const DEFAULT_MAX_DEPTH: usize = 1000;
+
+/// Native stack left when an evaluation moves to a new piece of stack, more than one evaluation
+/// takes before reaching the next one even in debug builds
+const RED_ZONE: usize = 64 * 1024;
+
+/// Size of the pieces of stack added when the native stack runs out
+const STACK_CHUNK: usize = 2 * 1024 * 1024;
+
+/// The nolang calls being run, innermost last
+struct CallStack {
+    frames: Vec<TraceFrame>,
+    max_depth: usize,
+}
+
+impl CallStack {
+    fn new(max_depth: usize) -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self {
+            frames: Vec::new(),
+            max_depth,
+        }))
+    }
+
+    /// Enter a call to `function` made at `span`, unless too many calls are running already
+    fn push(&mut self, function: &str, span: &Span) -> Result<()> {
+        if self.frames.len() >= self.max_depth {
+            let error = crate::error!(StackOverflow, span.clone(); "maximum recursion depth of {} exceeded", self.max_depth);
+            return Err(error.with_trace(self.trace()));
+        }
+        self.frames.push(TraceFrame {
+            function: function.to_string(),
+            span: span.clone(),
+        });
+        Ok(())
+    }
+
+    fn trace(&self) -> Vec<TraceFrame> {
+        self.frames.iter().rev().cloned().collect()
+    }
+}
+
 /// The Interpreter implementation
-struct Interpreter<'a> {
-    variables: Env<'a>,
+struct Interpreter {
+    variables: Env,
+    modules: Rc<RefCell<ModuleLoader>>, // shared by every module of the program
+    calls: Rc<RefCell<CallStack>>,
 }
 
-impl<'a> Interpreter<'a> {
+impl Interpreter {
+    fn new(variables: Env, max_depth: usize) -> Self {
+        Self {
+            variables,
+            modules: Default::default(),
+            calls: CallStack::new(max_depth),
+        }
+    }
+
+    /// An interpreter for the same program, running in another environment
+    fn child(&self, variables: Env) -> Self {
+        Self {
+            variables,
+            modules: self.modules.clone(),
+            calls: self.calls.clone(),
+        }
+    }
+
     /// check what's the current statement and send it for the correct evaluator
-    fn statement(&mut self, statement: Statement) -> Primitive {
-        match statement {
-            Statement::Op(op) => self.evaluate(&op),
-            Statement::Assign(var, value) => self.s_eval_assign(var, *value),
-            Statement::FuncAssign(name, arguments, block) => {
+    fn statement(&mut self, statement: Statement) -> Flow<Primitive> {
+        let span = statement.span;
+        match statement.kind {
+            StatementKind::Op(op) => self.evaluate(&op),
+            StatementKind::Assign(var, _, value) => self.s_eval_assign(var, *value),
+            StatementKind::Reassign(var, value) => self.s_eval_reassign(&var, *value),
+            StatementKind::FuncAssign(name, params, _, block) => {
+                let arguments = params.into_iter().map(|param| param.name).collect();
                 self.s_eval_func_assign(name, arguments, *block)
             }
+            StatementKind::Import(path, names) => self.s_eval_import(&path, names, &span),
+            StatementKind::Export(statement) => self.statement(*statement),
             #[allow(unreachable_patterns)]
             _ => unimplemented!(), // for when I implement new statements and want to test them on the parser
         }
+        .map_err(|e| e.or_at(&span))
     }
 
     fn s_eval_func_assign(
@@ -116,156 +257,422 @@ impl<'a> Interpreter<'a> {
         name: String,
         arguments: Vec<String>,
         block: Statement,
-    ) -> Primitive {
-        self.variables
-            .set(name, Primitive::Function(block, arguments));
+    ) -> Flow<Primitive> {
+        // the function captures the scope it's defined in, which also makes it see itself
+        let function = Function {
+            name: name.to_string(),
+            params: arguments,
+            body: block,
+            env: self.variables.clone(),
+        };
+        self.variables.set(name, Primitive::Function(Rc::new(function)));
+
+        Ok(Primitive::None)
+    }
+
+    /// `import "path"` binds the module to its name, `import a, b from "path"` binds the exports
+    fn s_eval_import(&mut self, path: &str, names: Vec<String>, span: &Span) -> Flow<Primitive> {
+        let module = self.import(path, span)?;
 
-        Primitive::None
+        if names.is_empty() {
+            self.variables
+                .set(module_name(Path::new(path)), Primitive::Module(module.clone()));
+        }
+        for name in names {
+            let value = module.get(&name)?;
+            self.variables.set(name, value);
+        }
+        Ok(Primitive::None)
+    }
+
+    /// Get a module from the cache, or run it in a new environment and save its exports
+    fn import(&mut self, path: &str, span: &Span) -> Result<Rc<Module>> {
+        let import = self.modules.borrow_mut().start(path, span)?;
+        let (key, program) = match import {
+            Import::Loaded(module) => return Ok(module),
+            Import::Run(key, program) => (key, program),
+        };
+
+        let names = exported_names(&program);
+        let mut runtime = self.child(Env::default());
+        let exports = program
+            .into_iter()
+            .try_for_each(|statement| runtime.statement(statement).map(drop).map_err(Unwind::into_error))
+            .and_then(|_| {
+                names
+                    .into_iter()
+                    .map(|name| Ok((name.to_string(), runtime.variables.get(&name)?)))
+                    .collect()
+            });
+
+        self.modules
+            .borrow_mut()
+            .finish(key, exports)
+            .map_err(|e| e.with_note("imported here", Some(span.clone())))
+    }
+
+    /// Run the statements in a new environment inside of `env`, returning the value of the last one
+    fn interpret_in(&self, statements: impl Iterator<Item = Statement>, env: &Env) -> Flow<Primitive> {
+        let mut runtime = self.child(Env::new(HashMap::new(), Some(env)));
+
+        let mut current = Ok(Primitive::None);
+        for statement in statements {
+            current = runtime.statement(statement);
+            if current.is_err() {
+                break;
+            }
+        }
+        runtime.variables.release();
+        current
     }
 
     /// evaluator for the block `do <Statement>;* done`
-    fn eval_block(&mut self, statements: Vec<Statement>) -> Primitive {
-        interpret(statements.into_iter(), Some(&self.variables))
+    fn eval_block(&mut self, statements: Vec<Statement>) -> Flow<Primitive> {
+        self.interpret_in(statements.into_iter(), &self.variables)
     }
 
-    fn eval_if(&mut self, condition: &Op, block: Op, else_block: Op) -> Primitive {
-        if self.evaluate(condition).to_bool() {
+    fn eval_if(&mut self, condition: &Op, block: Op, else_block: Op) -> Flow<Primitive> {
+        if self.evaluate(condition)?.to_bool() {
             self.evaluate(&block)
         } else {
             self.evaluate(&else_block)
         }
     }
 
+    /// Run the body of a loop once, telling if the loop must stop because of a `break`
+    fn loop_body(&mut self, body: &Op) -> Flow<bool> {
+        match self.evaluate(body) {
+            Ok(_) | Err(Unwind::Continue) => Ok(true),
+            Err(Unwind::Break) => Ok(false),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// `while <cond> do ... end`, its value is `none`
+    fn eval_while(&mut self, condition: &Op, body: &Op) -> Flow<Primitive> {
+        while self.evaluate(condition)?.to_bool() {
+            if !self.loop_body(body)? {
+                break;
+            }
+        }
+        Ok(Primitive::None)
+    }
+
+    /// `for <name> in <iterable> do ... end`, each iteration binds `name` in a new environment, so
+    /// the functions created in the body keep the value of that iteration
+    fn eval_for(&mut self, name: &str, iterable: &Op, body: &Op) -> Flow<Primitive> {
+        let items = self.evaluate(iterable)?.items().map_err(|e| e.or_at(&iterable.span))?;
+
+        for item in items {
+            let env = Env::new(std::iter::once((name.to_string(), item)).collect(), Some(&self.variables));
+            let mut runtime = self.child(env);
+            let next = runtime.loop_body(body);
+            runtime.variables.release();
+            if !next? {
+                break;
+            }
+        }
+        Ok(Primitive::None)
+    }
+
     /// Assignment `let x = <OP>;` evaluator
-    fn s_eval_assign(&mut self, var: String, value: Op) -> Primitive {
-        let value = self.evaluate(&value);
+    fn s_eval_assign(&mut self, var: String, value: Op) -> Flow<Primitive> {
+        let value = self.evaluate(&value)?;
 
         self.variables.set(var, value);
-        Primitive::None
+        Ok(Primitive::None)
+    }
+
+    /// Reassignment `x = <OP>` evaluator, the variable must already exist
+    fn s_eval_reassign(&mut self, var: &str, value: Op) -> Flow<Primitive> {
+        let value = self.evaluate(&value)?;
+
+        self.variables.assign(var, value)?;
+        Ok(Primitive::None)
     }
 
     /// Eval primary expressions, that are just the minimal possible expression
-    fn eval_primary(&mut self, prim: &Literal) -> Primitive {
+    fn eval_primary(&mut self, prim: &Literal) -> Flow<Primitive> {
         match prim {
-            Literal::Bool(b) => Primitive::Bool(*b),
-            Literal::None => Primitive::None,
-            Literal::String(ref s) => Primitive::Str(s.to_string()),
+            Literal::Bool(b) => Ok(Primitive::Bool(*b)),
+            Literal::None => Ok(Primitive::None),
+            Literal::String(ref s) => Ok(Primitive::Str(s.to_string())),
             Literal::Operation(ref op) => self.evaluate(op),
-            Literal::Num(n) => Primitive::Num(*n),
-            Literal::VarNormal(v) => self.variables.get(v),
+            Literal::Num(n) => Ok(Primitive::Num(*n)),
+            Literal::Int(n) => Ok(Primitive::Int(*n)),
+            Literal::BigInt(n) => Ok(Primitive::big_int(n.clone())),
+            Literal::Decimal(n) => Ok(Primitive::decimal(n.clone())),
+            Literal::VarNormal(v) => Ok(self.variables.get(v)?),
             #[allow(unreachable_patterns)]
             _ => todo!(), // for when I add a new primary operator to the parser
         }
     }
 
     /// Unary expression evaluator
-    fn eval_unary(&mut self, op: &Tok, right: Literal) -> Primitive {
+    fn eval_unary(&mut self, op: &Tok, right: Literal, span: &Span) -> Flow<Primitive> {
+        let right = self.evaluate(&Op::new(OpKind::Primary(Box::new(right)), span.clone()))?;
         match op {
-            Tok::Minus => -self.evaluate(&Op::Primary(Box::new(right))),
-            Tok::Not => Primitive::Bool(!self.evaluate(&Op::Primary(Box::new(right)))),
+            Tok::Minus => Ok((-right)?),
+            Tok::Not => Ok(Primitive::Bool(!right)),
             _ => unreachable!(),
         }
     }
 
     /// binary expression evaluator, like `1+1` or `1*1`
-    fn eval_binary(&mut self, left: Op, op: &Tok, right: Op) -> Primitive {
+    fn eval_binary(&mut self, left: Op, op: &Tok, right: Op) -> Flow<Primitive> {
+        use std::cmp::Ordering::*;
+
+        // Logical operators short-circuit, so the right side is evaluated lazily
         match op {
+            Tok::And | Tok::Or => {
+                let left = self.evaluate(&left)?;
+                return match left.to_bool() == matches!(op, Tok::And) {
+                    true => self.evaluate(&right),
+                    false => Ok(left),
+                };
+            }
+            _ => (),
+        }
+
+        let left = self.evaluate(&left)?;
+        let right = self.evaluate(&right)?;
+
+        let result = match op {
             // operations
-            Tok::Plus => self.evaluate(&right) + self.evaluate(&left),
-            Tok::Minus => self.evaluate(&right) - self.evaluate(&left),
-            Tok::Asterisk => self.evaluate(&right) * self.evaluate(&left),
-            Tok::Slash => self.evaluate(&right) / self.evaluate(&left),
-            Tok::Percent => self.evaluate(&right) % self.evaluate(&left),
+            Tok::Plus => left + right,
+            Tok::Minus => left - right,
+            Tok::Asterisk => left * right,
+            Tok::Slash => left / right,
+            Tok::SlashSlash => left.floor_div(right),
+            Tok::Percent => left % right,
 
-            Tok::Pow => self.evaluate(&left).pow(self.evaluate(&right)),
+            Tok::Pow => left.pow(right),
 
             // Comparisons
-            Tok::Comp => (self.evaluate(&right) == self.evaluate(&left)).into_pri(),
-            Tok::Different => (self.evaluate(&right) != self.evaluate(&left)).into_pri(),
+            Tok::Comp => Ok(left.equals(&right)?.into_pri()),
+            Tok::Different => Ok((!left.equals(&right)?).into_pri()),
 
-            Tok::Gt => (self.evaluate(&right) > self.evaluate(&left)).into_pri(),
-            Tok::GtOrEq => (self.evaluate(&right) >= self.evaluate(&left)).into_pri(),
+            Tok::Gt => Ok(matches!(left.compare(&right)?, Some(Greater)).into_pri()),
+            Tok::GtOrEq => Ok(matches!(left.compare(&right)?, Some(Greater | Equal)).into_pri()),
 
-            Tok::Lt => (self.evaluate(&right) < self.evaluate(&left)).into_pri(),
-            Tok::LtOrEq => (self.evaluate(&right) <= self.evaluate(&left)).into_pri(),
-
-            // Logical operators
-            Tok::And => self.evaluate(&left).and(&mut || self.evaluate(&right)),
-            Tok::Or => self.evaluate(&left).or(&mut || self.evaluate(&right)),
+            Tok::Lt => Ok(matches!(left.compare(&right)?, Some(Less)).into_pri()),
+            Tok::LtOrEq => Ok(matches!(left.compare(&right)?, Some(Less | Equal)).into_pri()),
 
             // should not reach this since I've covered all binary operations
             _ => unreachable!(),
+        };
+        Ok(result?)
+    }
+
+    /// Run the body of a nolang function in the environment of the call. Calls in tail position
+    /// unwind back to here and are run in the same loop, so tail recursion doesn't grow the
+    /// native stack
+    fn call_function(&mut self, function: Rc<Function>, env: Env) -> Flow<Primitive> {
+        let mut call = (function, env);
+        loop {
+            let (function, env) = call;
+            let mut runtime = self.child(env);
+            let result = runtime.tail_statement(&function.body);
+
+            // a function made by the caller can be the last thing that needed the caller's scope,
+            // when it was called in tail position
+            let captured = function.env.clone();
+            drop((runtime, function));
+            captured.release();
+
+            match result {
+                Err(Unwind::TailCall(next)) => {
+                    // the call keeps the frame, and where it was made, of the call it replaces
+                    if let Some(top) = self.calls.borrow_mut().frames.last_mut() {
+                        top.function = next.0.name.to_string();
+                    }
+                    call = *next;
+                }
+                Err(Unwind::Return(value)) => return Ok(*value),
+                result => return result,
+            }
         }
     }
 
-    fn eval_call(&mut self, called: &Op, arguments: Vec<Op>) -> Primitive {
+    /// Evaluate the called value and its arguments, from left to right, and then call it. In
+    /// tail position, nolang functions are left to the function call being run
+    fn eval_call(&mut self, called: &Op, arguments: &[Op], tail: bool, span: &Span) -> Flow<Primitive> {
+        let called = self.evaluate(called)?;
+
+        let mut values = Vec::with_capacity(arguments.len());
+        for argument in arguments {
+            values.push(self.evaluate(argument)?);
+        }
+        self.call(called, values, tail, span)
+    }
+
+    /// Call a value with the arguments, `span` is where the call was made
+    fn call(&mut self, called: Primitive, values: Vec<Primitive>, tail: bool, span: &Span) -> Flow<Primitive> {
         match called {
-            Op::Primary(p) => match &**p {
-                Literal::VarNormal(p) => match self.variables.get(p) {
-                    Primitive::Function(block, args) => {
-                        let env = args.iter().enumerate().map(|(index, key)| (
-                            key.to_string(),
-                            self.evaluate(arguments.get(index).unwrap_or_else(
-                                || crate::error!("CallError"; "Missing arguments for function call" => 1)
-                            ))
-                        )).collect::<HashMap<_, _>>();
-
-                        let env = Env::new(env, Some(&self.variables));
-
-                        interpret(std::iter::once(block), Some(&env))
-                    }
-                    Primitive::NativeFunc(func) => {
-                        if !matches!(arguments.len(), 1 | 0) {
-                            crate::error!("CallError"; "unwrong number of arguments passed for native function" => 1);
-                        }
-                        func(self.evaluate(&arguments[0]))
+            Primitive::Function(function) => {
+                let env = Env::call(&function.env, &function.params, values)?;
+                if tail {
+                    return Err(Unwind::TailCall(Box::new((function, env))));
+                }
+
+                self.calls.borrow_mut().push(&function.name, span)?;
+                let result = self.call_function(function, env).map_err(|unwind| match unwind {
+                    // the innermost call the error went through has the whole stack
+                    Unwind::Error(error) if error.trace.is_empty() => {
+                        Unwind::Error(error.with_trace(self.calls.borrow().trace()))
                     }
-                    e => crate::error!("CallError"; "can't call `{}`", e => 1),
-                },
-                _ => unreachable!(),
+                    unwind => unwind,
+                });
+                self.calls.borrow_mut().frames.pop();
+                result
+            }
+            Primitive::NativeFunc(native) => Ok(native.call(&values)?),
+            e => Err(crate::error!(Call; "can't call `{}`", e).into()),
+        }
+    }
+
+    /// Run a statement whose value is the value of the function being run
+    fn tail_statement(&mut self, statement: &Statement) -> Flow<Primitive> {
+        match statement.kind {
+            StatementKind::Op(ref op) => self.evaluate_tail(op),
+            _ => self.statement(statement.clone()),
+        }
+    }
+
+    /// Evaluate an operation in tail position, where its value is the value of the function being
+    /// run: the branches of an `if`, the last statement of a block and the value of a `return`
+    fn evaluate_tail(&mut self, operation: &Op) -> Flow<Primitive> {
+        match operation.kind {
+            OpKind::Call(ref called, ref arguments) => self.eval_call(called, arguments, true, &operation.span),
+
+            OpKind::If(ref cond, ref block, ref else_block) => match self.evaluate(cond)?.to_bool() {
+                true => self.evaluate_tail(block),
+                false => self.evaluate_tail(else_block),
             },
-            Op::Call(called, args) => {
-                match self.eval_call(called, args.to_vec()) {
-                    Primitive::Function(block, f_args) => {
-                        let env = f_args.iter().enumerate().map(|(index, key)| (
-                            key.to_string(),
-                            self.evaluate(arguments.get(index).unwrap_or_else(
-                                || crate::error!("CallError"; "Missing arguments for function call" => 1)
-                            ))
-                        )).collect::<HashMap<_, _>>();
-
-                        let env = Env::new(env, Some(&self.variables));
-
-                        interpret(std::iter::once(block), Some(&env))
+
+            OpKind::Grouping(ref op) => self.evaluate_tail(op),
+
+            OpKind::Block(ref statements) => {
+                let mut runtime = self.child(Env::new(HashMap::new(), Some(&self.variables)));
+
+                let mut current = Ok(Primitive::None);
+                for (index, statement) in statements.iter().enumerate() {
+                    current = match index + 1 == statements.len() {
+                        true => runtime.tail_statement(statement),
+                        false => runtime.statement(statement.clone()),
+                    };
+                    if current.is_err() {
+                        break;
                     }
-                    e => crate::error!("CallError"; "can't call value {}", e => 1)
                 }
+                runtime.variables.release();
+                current
             }
-            _ => unreachable!(),
+
+            _ => self.evaluate(operation),
         }
+        .map_err(|e| e.or_at(&operation.span))
     }
 
-    /// Minimal wrapper that sends the Op to the correct evaluator
-    fn evaluate(&mut self, operation: &Op) -> Primitive {
+    fn evaluate_or_none(&mut self, operation: &Option<Box<Op>>) -> Flow<Primitive> {
         match operation {
-            Op::Primary(ref value) => self.eval_primary(&**value),
+            Some(op) => self.evaluate(op),
+            None => Ok(Primitive::None),
+        }
+    }
+
+    /// Evaluate an operation. Nolang calls and nested operations recurse on the native stack, which
+    /// grows when it's about to run out, so only the depth limit stops a deep recursion, on any
+    /// thread
+    fn evaluate(&mut self, operation: &Op) -> Flow<Primitive> {
+        stacker::maybe_grow(RED_ZONE, STACK_CHUNK, || self.evaluate_op(operation))
+    }
+
+    /// Minimal wrapper that sends the Op to the correct evaluator
+    fn evaluate_op(&mut self, operation: &Op) -> Flow<Primitive> {
+        match operation.kind {
+            OpKind::Primary(ref value) => self.eval_primary(value),
 
-            Op::Unary(ref op, ref right) => self.eval_unary(op, *right.clone()),
+            OpKind::Unary(ref op, ref right) => self.eval_unary(op, *right.clone(), &operation.span),
 
-            Op::Binary(ref left, ref op, ref right) => self.eval_binary(*left.clone(), op, *right.clone()),
+            OpKind::Binary(ref left, ref op, ref right) => self.eval_binary(*left.clone(), op, *right.clone()),
 
-            Op::Grouping(ref op) => self.evaluate(op),
+            OpKind::Grouping(ref op) => self.evaluate(op),
 
-            Op::Call(ref called, ref arguments) => self.eval_call(&*called, arguments.clone()),
+            OpKind::List(ref items) => items
+                .iter()
+                .map(|item| self.evaluate(item))
+                .collect::<Flow<_>>()
+                .map(Primitive::list),
 
-            Op::If(ref cond, ref block, ref else_block) => self.eval_if(cond, *block.clone(), *else_block.clone()),
+            OpKind::Template(ref parts) => parts
+                .iter()
+                .map(|part| Ok(self.evaluate(part)?.to_string()))
+                .collect::<Flow<String>>()
+                .map(Primitive::Str),
+
+            // the errors of indexing and slicing go on to get the span of the whole operation
+            OpKind::Index(ref value, ref index) => {
+                let value = self.evaluate(value)?;
+                let index = self.evaluate(index)?;
+                value.index(&index).map_err(Unwind::from)
+            }
+
+            OpKind::Slice(ref value, ref start, ref end) => {
+                let value = self.evaluate(value)?;
+                let start = self.evaluate_or_none(start)?;
+                let end = self.evaluate_or_none(end)?;
+                value.slice(&start, &end).map_err(Unwind::from)
+            }
+
+            OpKind::Lambda(ref params, _, ref body) => {
+                let function = Function {
+                    name: "<lambda>".to_string(),
+                    params: params.iter().map(|param| param.name.to_string()).collect(),
+                    body: *body.clone(),
+                    env: self.variables.clone(),
+                };
+                Ok(Primitive::Function(Rc::new(function)))
+            }
+
+            OpKind::Call(ref called, ref arguments) => self.eval_call(called, arguments, false, &operation.span),
+
+            OpKind::If(ref cond, ref block, ref else_block) => self.eval_if(cond, *block.clone(), *else_block.clone()),
+
+            OpKind::Block(ref block) => self.eval_block(block.clone()),
+
+            OpKind::Access(ref value, ref name) => self.evaluate(value)?.field(name).map_err(Unwind::from),
+
+            OpKind::Map(ref entries) => entries
+                .iter()
+                .map(|(key, value)| Ok((key.to_string(), self.evaluate(value)?)))
+                .collect::<Flow<BTreeMap<_, _>>>()
+                .map(Primitive::map),
+
+            OpKind::While(ref condition, ref body) => self.eval_while(condition, body),
+
+            OpKind::For(ref name, ref iterable, ref body) => self.eval_for(name, iterable, body),
+
+            OpKind::Break => Err(Unwind::Break),
+
+            OpKind::Continue => Err(Unwind::Continue),
+
+            // `return` leaves the function, so its value is in tail position
+            OpKind::Return(ref value) => {
+                let value = match value {
+                    Some(value) => self.evaluate_tail(value)?,
+                    None => Primitive::None,
+                };
+                Err(Unwind::Return(Box::new(value)))
+            }
 
-            Op::Block(ref block) => self.eval_block(block.clone()),
+            OpKind::Error => Err(crate::error!(Runtime; "can't run code that failed to parse").into()),
 
             #[allow(unreachable_patterns)]
             // for when I add a new Operation and want to test the parser before going to the
             // interpreter
             _ => unimplemented!(),
         }
+        .map_err(|e| e.or_at(&operation.span))
     }
 }
@@ -0,0 +1,72 @@
+use super::Primitive;
+use crate::error::Result;
+use std::fmt;
+
+/// How many arguments a native function takes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Fixed(usize),
+    Range(usize, usize), // both ends included
+    Variadic(usize),     // any number of arguments from the given minimum
+}
+
+impl Arity {
+    /// Check that a call to `name` got a number of arguments this arity allows
+    pub fn check(self, name: &str, count: usize) -> Result<()> {
+        let (allowed, expected) = match self {
+            Self::Fixed(n) => (count == n, format!("{} argument{}", n, plural(n))),
+            Self::Range(min, max) => (min <= count && count <= max, format!("{} to {} arguments", min, max)),
+            Self::Variadic(min) => (count >= min, format!("at least {} argument{}", min, plural(min))),
+        };
+
+        match allowed {
+            true => Ok(()),
+            false => Err(crate::error!(Call; "`{}` takes {} but {} were given", name, expected, count)),
+        }
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// The Rust code run by a native function
+pub type NativeFn = dyn Fn(&[Primitive]) -> Result<Primitive>;
+
+/// A function written in Rust that nolang programs can call. It can hold state it captured, and
+/// is only called with a number of arguments its arity allows
+pub struct NativeFunction {
+    pub name: String,
+    pub arity: Arity,
+    func: Box<NativeFn>,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arity: Arity, func: impl Fn(&[Primitive]) -> Result<Primitive> + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            arity,
+            func: Box::new(func),
+        }
+    }
+
+    /// Check the number of arguments and run the function
+    pub fn call(&self, arguments: &[Primitive]) -> Result<Primitive> {
+        self.arity.check(&self.name, arguments.len())?;
+        (self.func)(arguments)
+    }
+}
+
+impl fmt::Debug for NativeFunction {
+    // the boxed closure can't be printed
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("NativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
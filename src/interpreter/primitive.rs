@@ -1,34 +1,54 @@
-use super::Statement;
-use crate::error;
-use std::{cmp, fmt, ops};
+use super::bignum::{decimal_floor_div_rem, divide, floor_div_rem, power, rounded};
+use super::{Env, NativeFunction, Statement};
+use crate::vm::Closure;
+use crate::error::{NolangError, Result};
+use crate::modules::Module;
+use bigdecimal::{BigDecimal, Signed, ToPrimitive, Zero};
+use num_bigint::BigInt;
+use std::{cell::RefCell, cmp, collections::BTreeMap, convert::TryFrom, fmt, ops, rc::Rc};
 
 /// Nolang primitive types
 #[derive(Debug, Clone)]
 pub enum Primitive {
     Num(f64),
+    Int(i64),
+    BigInt(Rc<BigInt>),      // integer with any number of digits
+    Decimal(Rc<BigDecimal>), // exact decimal, rounded to the precision set by `decimal_precision`
     Str(String),
     Bool(bool),
-    Function(Statement, Vec<String>),
-    NativeFunc(fn(arg: Primitive) -> Primitive),
+    Function(Rc<Function>),
+    NativeFunc(Rc<NativeFunction>),
+    Compiled(Rc<Closure>), // function compiled to bytecode, used by the vm
+    Module(Rc<Module>),
+    List(Rc<RefCell<Vec<Primitive>>>), // shared, so `push` changes every reference to the list
+    Map(Rc<RefCell<BTreeMap<String, Primitive>>>),
     None,
 }
 
+/// A function written in nolang, run by the tree-walker. Its scope keeps it alive when it's stored
+/// there, the scope is freed once the code that made it is done, unless the function is kept
+/// somewhere else too, like in a list
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    pub body: Statement,
+    pub env: Env, // the scope it captured
+}
+
 pub trait IntoPrimitive {
     fn into_pri(self) -> Primitive;
 }
 
+/// Conversion from a nolang value to a Rust type, failing with a TypeError when the value has
+/// another type
+pub trait FromPrimitive: Sized {
+    fn from_pri(value: Primitive) -> Result<Self>;
+}
+
 impl fmt::Display for Primitive {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let raw = match self {
-            Self::Bool(true) => "true".to_string(),
-            Self::Bool(false) => "false".to_string(),
-            Self::None => "none".to_string(),
-            Self::Str(s) => s.to_string(),
-            Self::Num(ref n) => n.to_string(),
-            Self::Function(..) => "<function>".to_string(),
-            Self::NativeFunc(..) => "<native function>".to_string(),
-        };
-        write!(f, "{}", raw)
+        write!(f, "{}", self.shown(&mut Vec::new()))
     }
 }
 
@@ -42,112 +62,223 @@ impl ops::Not for Primitive {
 }
 
 impl ops::Neg for Primitive {
-    type Output = Self;
+    type Output = Result<Self>;
 
     #[inline]
     fn neg(self) -> Self::Output {
         match self {
-            Self::Num(n) => Self::Num(-n),
-            _ => error!("TypeError"; "can't use `-` operator with {}", self => 1),
+            Self::Num(n) => Ok(Self::Num(-n)),
+            Self::Int(n) => n.checked_neg().map(Self::Int).ok_or_else(|| overflow("-", &self, &self)),
+            Self::BigInt(ref n) => Ok(Self::big_int(-n.as_ref().clone())),
+            Self::Decimal(ref n) => Ok(Self::decimal(-n.as_ref().clone())),
+            _ => Err(crate::error!(Type; "can't use `-` operator with {}", self)),
         }
     }
 }
 
 impl ops::Add for Primitive {
-    type Output = Self;
+    type Output = Result<Self>;
 
     #[inline]
     fn add(self, rhs: Self) -> Self::Output {
-        match (&rhs, &self) {
-            (Self::Num(o_num), Self::Num(s_num)) => Self::Num(o_num + s_num),
+        match numbers(&self, &rhs) {
+            Some(Numbers::Ints(a, b)) => return a.checked_add(b).map(Self::Int).ok_or_else(|| overflow("+", &self, &rhs)),
+            Some(Numbers::Bigs(a, b)) => return Ok(Self::big_int(a + b)),
+            Some(Numbers::Decimals(a, b)) => return Ok(Self::decimal(rounded(a + b))),
+            Some(Numbers::Floats(a, b)) => return Ok(Self::Num(a + b)),
+            None => (),
+        }
 
-            (Self::Str(o_str), Self::Str(s_str)) => Self::Str(o_str.to_string() + s_str),
-            _ => error!("TypeError"; "tried to use `+` operator between {} and {}", rhs, self => 1),
+        match (&self, &rhs) {
+            (Self::Str(s_str), Self::Str(o_str)) => Ok(Self::Str(s_str.to_string() + o_str)),
+
+            (Self::List(s_list), Self::List(o_list)) => {
+                let mut list = s_list.borrow().clone();
+                list.extend(o_list.borrow().iter().cloned());
+                Ok(Self::list(list))
+            }
+            _ => Err(crate::error!(Type; "tried to use `+` operator between {} and {}", self, rhs)),
         }
     }
 }
 
 impl ops::Sub for Primitive {
-    type Output = Primitive;
+    type Output = Result<Self>;
 
     #[inline]
     fn sub(self, rhs: Self) -> Self::Output {
-        match (&rhs, &self) {
-            (Self::Num(o_num), Self::Num(s_num)) => Self::Num(o_num - s_num),
-            _ => error!("TypeError"; "tried to use `-` operator between {} and {}", rhs, self => 1),
+        match numbers(&self, &rhs) {
+            Some(Numbers::Ints(a, b)) => a.checked_sub(b).map(Self::Int).ok_or_else(|| overflow("-", &self, &rhs)),
+            Some(Numbers::Bigs(a, b)) => Ok(Self::big_int(a - b)),
+            Some(Numbers::Decimals(a, b)) => Ok(Self::decimal(rounded(a - b))),
+            Some(Numbers::Floats(a, b)) => Ok(Self::Num(a - b)),
+            None => Err(crate::error!(Type; "tried to use `-` operator between {} and {}", self, rhs)),
         }
     }
 }
 
 impl ops::Mul for Primitive {
-    type Output = Self;
+    type Output = Result<Self>;
 
     #[inline]
     fn mul(self, rhs: Self) -> Self::Output {
-        match (&rhs, &self) {
-            (Self::Num(o_num), Self::Num(s_num)) => Self::Num(o_num * s_num),
-            _ => error!("TypeError"; "tried to use `*` operator between {} and {}", rhs, self => 1),
+        match numbers(&self, &rhs) {
+            Some(Numbers::Ints(a, b)) => a.checked_mul(b).map(Self::Int).ok_or_else(|| overflow("*", &self, &rhs)),
+            Some(Numbers::Bigs(a, b)) => Ok(Self::big_int(a * b)),
+            Some(Numbers::Decimals(a, b)) => Ok(Self::decimal(rounded(a * b))),
+            Some(Numbers::Floats(a, b)) => Ok(Self::Num(a * b)),
+            None => Err(crate::error!(Type; "tried to use `*` operator between {} and {}", self, rhs)),
         }
     }
 }
 
 impl ops::Div for Primitive {
-    type Output = Self;
+    type Output = Result<Self>;
 
     #[inline]
     fn div(self, rhs: Self) -> Self::Output {
-        match (&rhs, &self) {
-            (Self::Num(o_num), Self::Num(s_num)) => Self::Num(o_num / s_num),
-            _ => error!("TypeError"; "tried to use `/` operator between {} and {}", rhs, self => 1),
+        // `/` divides integers as floats and big integers as decimals, `//` keeps them integers
+        match numbers(&self, &rhs) {
+            Some(Numbers::Ints(a, b)) => Ok(Self::Num(a as f64 / b as f64)),
+            Some(Numbers::Bigs(_, b)) if b.is_zero() => Err(division_by_zero("/", &self, &rhs)),
+            Some(Numbers::Bigs(a, b)) => Ok(Self::decimal(divide(&a.into(), &b.into()))),
+            Some(Numbers::Decimals(_, b)) if b.is_zero() => Err(division_by_zero("/", &self, &rhs)),
+            Some(Numbers::Decimals(a, b)) => Ok(Self::decimal(divide(&a, &b))),
+            Some(Numbers::Floats(a, b)) => Ok(Self::Num(a / b)),
+            None => Err(crate::error!(Type; "tried to use `/` operator between {} and {}", self, rhs)),
         }
     }
 }
 
 impl ops::Rem for Primitive {
-    type Output = Self;
+    type Output = Result<Self>;
 
     #[inline]
     fn rem(self, rhs: Self) -> Self::Output {
-        match (&rhs, &self) {
-            (Self::Num(o_num), Self::Num(s_num)) => Self::Num(o_num % s_num),
-            _ => error!("TypeError"; "tried to use `%` operator between {} and {}", rhs, self => 1),
+        // the remainder has the sign of the divisor, like `//` rounds down, so `-7 % 3` is 2 and
+        // `-7.5 % 2` is 0.5
+        match numbers(&self, &rhs) {
+            Some(Numbers::Ints(_, 0)) => Err(division_by_zero("%", &self, &rhs)),
+            Some(Numbers::Ints(a, b)) => Ok(Self::Int(a.wrapping_rem(b)).floored(b)),
+            Some(Numbers::Bigs(_, b)) if b.is_zero() => Err(division_by_zero("%", &self, &rhs)),
+            Some(Numbers::Bigs(a, b)) => Ok(Self::big_int(floor_div_rem(&a, &b).1)),
+            Some(Numbers::Decimals(_, b)) if b.is_zero() => Err(division_by_zero("%", &self, &rhs)),
+            Some(Numbers::Decimals(a, b)) => Ok(Self::decimal(decimal_floor_div_rem(&a, &b).1)),
+            Some(Numbers::Floats(a, b)) => match a % b {
+                0.0 => Ok(Self::Num(0.0_f64.copysign(b))),
+                r if (r < 0.0) != (b < 0.0) => Ok(Self::Num(r + b)),
+                r => Ok(Self::Num(r)),
+            },
+            None => Err(crate::error!(Type; "tried to use `%` operator between {} and {}", self, rhs)),
         }
     }
 }
 
-impl cmp::PartialEq for Primitive {
-    #[inline]
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Num(o_num), Self::Num(s_num)) => o_num == s_num,
-            (Self::Bool(s_bool), Self::Bool(o_bool)) => s_bool == o_bool,
-            (Self::None, Self::None) => true,
-            _ => error!("TypeError"; "can't compare {} with {} using == or ~=", self, other => 1),
+impl Primitive {
+    /// Name of the value's type, as returned by `typeof`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::NativeFunc(..) | Self::Function(..) | Self::Compiled(..) => "Function",
+            Self::None => "None",
+            Self::Num(..) => "Num",
+            Self::Int(..) => "Int",
+            Self::BigInt(..) => "BigInt",
+            Self::Decimal(..) => "Decimal",
+            Self::Bool(..) => "Bool",
+            Self::Str(..) => "Str",
+            Self::Module(..) => "Module",
+            Self::List(..) => "List",
+            Self::Map(..) => "Map",
         }
     }
-}
 
-impl cmp::PartialOrd for Primitive {
-    #[inline]
-    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+    /// Equality used by `==` and `~=`, comparing values of different types is a TypeError
+    pub fn equals(&self, other: &Self) -> Result<bool> {
+        self.equals_inside(other, &mut Vec::new())
+    }
+
+    /// Equality of values inside of the pairs of lists or maps in `comparing`. A pair that is
+    /// compared again inside of itself is equal so far, so lists that contain themselves compare
+    fn equals_inside(&self, other: &Self, comparing: &mut Vec<(*const (), *const ())>) -> Result<bool> {
+        if let Some(order) = number_order(self, other) {
+            return Ok(order == Some(cmp::Ordering::Equal));
+        }
+
         match (self, other) {
-            (Self::Num(s_num), Self::Num(o_num)) => o_num.partial_cmp(s_num),
-            (Self::Str(s_str), Self::Str(o_str)) => o_str.partial_cmp(s_str),
-            (Self::Bool(s_bool), Self::Bool(o_bool)) => o_bool.partial_cmp(s_bool),
-            _ => {
-                error!("TypeError"; "can't compare {} with {} using <, >, <=, >=", self, other => 1)
+            (Self::Str(s_str), Self::Str(o_str)) => Ok(s_str == o_str),
+            (Self::Bool(s_bool), Self::Bool(o_bool)) => Ok(s_bool == o_bool),
+            (Self::None, Self::None) => Ok(true),
+            (Self::List(s_list), Self::List(o_list)) => {
+                let pair = (Rc::as_ptr(s_list) as *const (), Rc::as_ptr(o_list) as *const ());
+                if Rc::ptr_eq(s_list, o_list) || comparing.contains(&pair) {
+                    return Ok(true);
+                }
+                let (s_list, o_list) = (s_list.borrow(), o_list.borrow());
+                if s_list.len() != o_list.len() {
+                    return Ok(false);
+                }
+                comparing.push(pair);
+                let mut equal = Ok(true);
+                for (s_item, o_item) in s_list.iter().zip(o_list.iter()) {
+                    equal = s_item.equals_inside(o_item, comparing);
+                    if !matches!(equal, Ok(true)) {
+                        break;
+                    }
+                }
+                comparing.pop();
+                equal
+            }
+            (Self::Map(s_map), Self::Map(o_map)) => {
+                let pair = (Rc::as_ptr(s_map) as *const (), Rc::as_ptr(o_map) as *const ());
+                if Rc::ptr_eq(s_map, o_map) || comparing.contains(&pair) {
+                    return Ok(true);
+                }
+                let (s_map, o_map) = (s_map.borrow(), o_map.borrow());
+                if s_map.len() != o_map.len() {
+                    return Ok(false);
+                }
+                comparing.push(pair);
+                let mut equal = Ok(true);
+                for ((s_key, s_value), (o_key, o_value)) in s_map.iter().zip(o_map.iter()) {
+                    equal = match s_key == o_key {
+                        true => s_value.equals_inside(o_value, comparing),
+                        false => Ok(false),
+                    };
+                    if !matches!(equal, Ok(true)) {
+                        break;
+                    }
+                }
+                comparing.pop();
+                equal
             }
+            _ => Err(crate::error!(Type; "can't compare {} with {} using == or ~=", self, other)),
+        }
+    }
+
+    /// Ordering used by `<`, `>`, `<=` and `>=`
+    pub fn compare(&self, other: &Self) -> Result<Option<cmp::Ordering>> {
+        if let Some(order) = number_order(self, other) {
+            return Ok(order);
+        }
+
+        match (self, other) {
+            (Self::Str(s_str), Self::Str(o_str)) => Ok(s_str.partial_cmp(o_str)),
+            (Self::Bool(s_bool), Self::Bool(o_bool)) => Ok(s_bool.partial_cmp(o_bool)),
+            _ => Err(crate::error!(Type; "can't compare {} with {} using <, >, <=, >=", self, other)),
         }
     }
-}
 
-impl Primitive {
     pub fn to_bool(&self) -> bool {
         match self {
             Self::Bool(false) => false,
             Self::None => false,
             Self::Num(x) if x.abs() < f64::EPSILON => false,
+            Self::Int(0) => false,
+            Self::BigInt(n) if n.is_zero() => false,
+            Self::Decimal(n) if n.is_zero() => false,
             Self::Str(b) if b.as_str() == "" => false,
+            Self::List(l) if l.borrow().is_empty() => false,
+            Self::Map(m) if m.borrow().is_empty() => false,
             _ => true,
         }
     }
@@ -155,30 +286,308 @@ impl Primitive {
     pub fn to_number(&self) -> Option<Self> {
         match self {
             Self::Num(n) => Some(Self::Num(*n)),
+            Self::Int(n) => Some(Self::Int(*n)),
+            Self::BigInt(_) | Self::Decimal(_) => Some(self.clone()),
             _ => None,
         }
     }
 
-    pub fn and(self, rhs: &mut dyn FnMut() -> Self) -> Self {
+    pub fn and(self, rhs: &mut dyn FnMut() -> Result<Self>) -> Result<Self> {
         match self.to_bool() {
             true => rhs(),
-            false => self,
+            false => Ok(self),
         }
     }
 
-    pub fn or(self, rhs: &mut dyn FnMut() -> Self) -> Self {
+    pub fn or(self, rhs: &mut dyn FnMut() -> Result<Self>) -> Result<Self> {
         match self.to_bool() {
-            true => self,
+            true => Ok(self),
             false => rhs(),
         }
     }
 
-    pub fn pow(&self, rhs: Self) -> Self {
-        match (&self, &rhs) {
-            (Self::Num(s_num), Self::Num(o_num)) => Primitive::Num(s_num.powf(*o_num)),
-            _ => crate::error!("RuntimeError"; "Can't apply operator `**` between {} and {}", &self, &rhs => 1)
+    pub fn list(items: Vec<Primitive>) -> Self {
+        Self::List(Rc::new(RefCell::new(items)))
+    }
+
+    pub fn map(entries: BTreeMap<String, Primitive>) -> Self {
+        Self::Map(Rc::new(RefCell::new(entries)))
+    }
+
+    pub fn big_int(n: BigInt) -> Self {
+        Self::BigInt(Rc::new(n))
+    }
+
+    pub fn decimal(n: BigDecimal) -> Self {
+        Self::Decimal(Rc::new(n))
+    }
+
+    /// How the value is printed. `inside` has the lists and maps it's in, a list or map that
+    /// contains itself is shown as `[...]` or `{...}` where it repeats
+    fn shown(&self, inside: &mut Vec<*const ()>) -> String {
+        match self {
+            Self::Bool(true) => "true".to_string(),
+            Self::Bool(false) => "false".to_string(),
+            Self::None => "none".to_string(),
+            Self::Str(s) => s.to_string(),
+            Self::Num(ref n) => n.to_string(),
+            Self::Int(ref n) => n.to_string(),
+            Self::BigInt(ref n) => n.to_string(),
+            Self::Decimal(ref n) => n.to_plain_string(),
+            Self::Function(..) => "<function>".to_string(),
+            Self::NativeFunc(..) => "<native function>".to_string(),
+            Self::Compiled(..) => "<function>".to_string(),
+            Self::Module(ref module) => format!("<module {}>", module.name),
+            Self::List(ref list) => {
+                let pointer = Rc::as_ptr(list) as *const ();
+                if inside.contains(&pointer) {
+                    return "[...]".to_string();
+                }
+                inside.push(pointer);
+                let items: Vec<String> = list.borrow().iter().map(|item| item.nested(inside)).collect();
+                inside.pop();
+                format!("[{}]", items.join(", "))
+            }
+            Self::Map(ref map) => {
+                let pointer = Rc::as_ptr(map) as *const ();
+                if inside.contains(&pointer) {
+                    return "{...}".to_string();
+                }
+                inside.push(pointer);
+                let entries: Vec<String> = map
+                    .borrow()
+                    .iter()
+                    .map(|(key, value)| format!("{:?}: {}", key, value.nested(inside)))
+                    .collect();
+                inside.pop();
+                format!("{{{}}}", entries.join(", "))
+            }
+        }
+    }
+
+    /// How the value is shown inside of a list or a map, with strings quoted
+    fn nested(&self, inside: &mut Vec<*const ()>) -> String {
+        match self {
+            Self::Str(s) => format!("{:?}", s),
+            value => value.shown(inside),
+        }
+    }
+
+    /// `value.name`, for modules and maps
+    pub fn field(&self, name: &str) -> Result<Self> {
+        match self {
+            Self::Module(module) => module.get(name),
+            Self::Map(map) => match map.borrow().get(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(crate::error!(Reference; "map has no key {:?}", name)),
+            },
+            _ => Err(crate::error!(Type; "can't access `{}` on `{}`", name, self)),
+        }
+    }
+
+    /// `value[index]`, indexes start at zero
+    pub fn index(&self, index: &Self) -> Result<Self> {
+        match self {
+            Self::List(list) => {
+                let list = list.borrow();
+                let position = to_index(index)?;
+                match list.get(position) {
+                    Some(item) => Ok(item.clone()),
+                    None => Err(crate::error!(Runtime; "index {} is out of range for a list of length {}", index, list.len())),
+                }
+            }
+            Self::Map(_) => match index {
+                Self::Str(key) => self.field(key),
+                _ => Err(crate::error!(Type; "map keys must be strings, found {}", index)),
+            },
+            // strings are indexed by character, not by byte
+            Self::Str(s) => match s.chars().nth(to_index(index)?) {
+                Some(c) => Ok(Self::Str(c.to_string())),
+                None => Err(crate::error!(Runtime; "index {} is out of range for a string of length {}", index, s.chars().count())),
+            },
+            _ => Err(crate::error!(Type; "can't index {}", self)),
+        }
+    }
+
+    /// `value[start:end]`, missing bounds are `none` and default to the start and the end
+    pub fn slice(&self, start: &Self, end: &Self) -> Result<Self> {
+        match self {
+            Self::List(list) => {
+                let list = list.borrow();
+                let (start, end) = slice_bounds(start, end, list.len(), "list")?;
+                Ok(Self::list(list[start..end].to_vec()))
+            }
+            Self::Str(s) => {
+                let (start, end) = slice_bounds(start, end, s.chars().count(), "string")?;
+                Ok(Self::Str(s.chars().skip(start).take(end - start).collect()))
+            }
+            _ => Err(crate::error!(Type; "can't slice {}", self)),
         }
     }
+
+    /// The values a `for` loop goes through: the items of a list, the characters of a string or
+    /// the keys of a map. They're copied before the loop starts, so changing the value inside of
+    /// the loop doesn't change the iteration
+    pub fn items(&self) -> Result<Vec<Self>> {
+        match self {
+            Self::List(list) => Ok(list.borrow().clone()),
+            Self::Str(s) => Ok(s.chars().map(|c| Self::Str(c.to_string())).collect()),
+            Self::Map(map) => Ok(map.borrow().keys().map(|key| Self::Str(key.to_string())).collect()),
+            _ => Err(crate::error!(Type; "can't iterate over {}", self)),
+        }
+    }
+
+    pub fn pow(&self, rhs: Self) -> Result<Self> {
+        // negative exponents make fractions, so their powers are floats
+        match numbers(self, &rhs) {
+            Some(Numbers::Ints(a, b)) if b >= 0 => u32::try_from(b)
+                .ok()
+                .and_then(|b| a.checked_pow(b))
+                .map(Primitive::Int)
+                .ok_or_else(|| overflow("**", self, &rhs)),
+            Some(Numbers::Ints(a, b)) => Ok(Primitive::Num((a as f64).powf(b as f64))),
+            // big integers and decimals stay exact, so their exponents must be integers
+            Some(Numbers::Bigs(_, b)) if b.is_negative() => Err(crate::error!(Runtime;
+                "the power of {} by a negative exponent isn't an integer, convert it with `decimal` first", self
+            )),
+            Some(Numbers::Bigs(a, b)) => match b.to_u32() {
+                Some(b) => Ok(Primitive::big_int(a.pow(b))),
+                None => Err(crate::error!(Runtime; "exponent {} is too big", b)),
+            },
+            Some(Numbers::Decimals(a, b)) => match (b.is_integer(), b.to_i64()) {
+                (true, Some(b)) if a.is_zero() && b < 0 => Err(division_by_zero("**", self, &rhs)),
+                (true, Some(b)) => Ok(Primitive::decimal(power(&a, b))),
+                (true, None) => Err(crate::error!(Runtime; "exponent {} is too big", b)),
+                (false, _) => Err(crate::error!(Runtime; "decimals can only be raised to integer powers, found {}", b)),
+            },
+            Some(Numbers::Floats(a, b)) => Ok(Primitive::Num(a.powf(b))),
+            None => Err(crate::error!(Runtime; "Can't apply operator `**` between {} and {}", &self, &rhs)),
+        }
+    }
+
+    /// `//`, division rounded down. Integers stay integers
+    pub fn floor_div(&self, rhs: Self) -> Result<Self> {
+        match numbers(self, &rhs) {
+            Some(Numbers::Ints(_, 0)) => Err(division_by_zero("//", self, &rhs)),
+            Some(Numbers::Ints(a, b)) => match a.checked_div(b) {
+                // rounding towards zero is one too much when the result is negative and not exact
+                Some(q) if a % b != 0 && (a < 0) != (b < 0) => Ok(Primitive::Int(q - 1)),
+                Some(q) => Ok(Primitive::Int(q)),
+                None => Err(overflow("//", self, &rhs)),
+            },
+            Some(Numbers::Bigs(_, b)) if b.is_zero() => Err(division_by_zero("//", self, &rhs)),
+            Some(Numbers::Bigs(a, b)) => Ok(Primitive::big_int(floor_div_rem(&a, &b).0)),
+            Some(Numbers::Decimals(_, b)) if b.is_zero() => Err(division_by_zero("//", self, &rhs)),
+            Some(Numbers::Decimals(a, b)) => Ok(Primitive::decimal(decimal_floor_div_rem(&a, &b).0)),
+            Some(Numbers::Floats(a, b)) => Ok(Primitive::Num((a / b).floor())),
+            None => Err(crate::error!(Type; "tried to use `//` operator between {} and {}", self, rhs)),
+        }
+    }
+
+    /// Move the remainder `self` of a division by `divisor` to have the sign of the divisor
+    fn floored(self, divisor: i64) -> Self {
+        match self {
+            Primitive::Int(r) if r != 0 && (r < 0) != (divisor < 0) => Primitive::Int(r + divisor),
+            value => value,
+        }
+    }
+}
+
+/// The operands of a numeric operator, both converted to the wider of their types: integers, then
+/// big integers, then decimals. Floats make the other operand a float, except for decimals
+enum Numbers {
+    Ints(i64, i64),
+    Bigs(BigInt, BigInt),
+    Decimals(BigDecimal, BigDecimal),
+    Floats(f64, f64),
+}
+
+fn numbers(left: &Primitive, right: &Primitive) -> Option<Numbers> {
+    match (left, right) {
+        (Primitive::Int(a), Primitive::Int(b)) => Some(Numbers::Ints(*a, *b)),
+        // exact decimals don't mix with floats, one of them has to be converted first
+        (Primitive::Decimal(_), Primitive::Num(_)) | (Primitive::Num(_), Primitive::Decimal(_)) => None,
+        (Primitive::Num(_), _) | (_, Primitive::Num(_)) => Some(Numbers::Floats(to_float(left)?, to_float(right)?)),
+        (Primitive::Decimal(_), _) | (_, Primitive::Decimal(_)) => {
+            Some(Numbers::Decimals(to_decimal(left)?, to_decimal(right)?))
+        }
+        _ => Some(Numbers::Bigs(to_big_int(left)?, to_big_int(right)?)),
+    }
+}
+
+/// How two numbers are ordered, `None` when they aren't numbers that can be compared
+fn number_order(left: &Primitive, right: &Primitive) -> Option<Option<cmp::Ordering>> {
+    let order = match numbers(left, right)? {
+        Numbers::Ints(a, b) => a.partial_cmp(&b),
+        Numbers::Bigs(a, b) => a.partial_cmp(&b),
+        Numbers::Decimals(a, b) => a.partial_cmp(&b),
+        Numbers::Floats(a, b) => a.partial_cmp(&b),
+    };
+    Some(order)
+}
+
+/// The value of any number as a float, rounded when it's exact
+pub(super) fn to_float(value: &Primitive) -> Option<f64> {
+    match value {
+        Primitive::Num(n) => Some(*n),
+        Primitive::Int(n) => Some(*n as f64),
+        Primitive::BigInt(n) => n.to_f64(),
+        Primitive::Decimal(n) => n.to_f64(),
+        _ => None,
+    }
+}
+
+/// The value of an integer as a big integer
+pub(super) fn to_big_int(value: &Primitive) -> Option<BigInt> {
+    match value {
+        Primitive::Int(n) => Some(BigInt::from(*n)),
+        Primitive::BigInt(n) => Some(n.as_ref().clone()),
+        _ => None,
+    }
+}
+
+/// The value of an integer or a decimal as a decimal
+pub(super) fn to_decimal(value: &Primitive) -> Option<BigDecimal> {
+    match value {
+        Primitive::Decimal(n) => Some(n.as_ref().clone()),
+        value => to_big_int(value).map(BigDecimal::from),
+    }
+}
+
+/// The error of an integer operation whose result doesn't fit in 64 bits
+fn overflow(operator: &str, left: &Primitive, right: &Primitive) -> NolangError {
+    crate::error!(Runtime; "integer overflow in {} {} {}", left, operator, right)
+}
+
+/// The error of a division of an integer or a decimal by zero
+fn division_by_zero(operator: &str, left: &Primitive, right: &Primitive) -> NolangError {
+    crate::error!(Runtime; "division by zero in {} {} {}", left, operator, right)
+}
+
+/// Convert a value used as an index to a position
+fn to_index(index: &Primitive) -> Result<usize> {
+    match index {
+        Primitive::Int(n) if *n >= 0 => Ok(*n as usize),
+        Primitive::Num(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        _ => Err(crate::error!(Type; "indexes must be whole numbers that aren't negative, found {}", index)),
+    }
+}
+
+/// The positions a slice goes from and to, in a value of length `len`
+fn slice_bounds(start: &Primitive, end: &Primitive, len: usize, what: &str) -> Result<(usize, usize)> {
+    let start = match start {
+        Primitive::None => 0,
+        start => to_index(start)?,
+    };
+    let end = match end {
+        Primitive::None => len,
+        end => to_index(end)?,
+    };
+
+    if start > end || end > len {
+        return Err(crate::error!(Runtime; "slice {}:{} is out of range for a {} of length {}", start, end, what, len));
+    }
+    Ok((start, end))
 }
 
 impl IntoPrimitive for bool {
@@ -201,3 +610,145 @@ impl IntoPrimitive for f64 {
         Primitive::Num(self)
     }
 }
+
+impl IntoPrimitive for i64 {
+    #[inline]
+    fn into_pri(self) -> Primitive {
+        Primitive::Int(self)
+    }
+}
+
+impl IntoPrimitive for BigInt {
+    #[inline]
+    fn into_pri(self) -> Primitive {
+        Primitive::big_int(self)
+    }
+}
+
+impl IntoPrimitive for BigDecimal {
+    #[inline]
+    fn into_pri(self) -> Primitive {
+        Primitive::decimal(self)
+    }
+}
+
+impl IntoPrimitive for &str {
+    #[inline]
+    fn into_pri(self) -> Primitive {
+        Primitive::Str(self.to_string())
+    }
+}
+
+impl IntoPrimitive for () {
+    #[inline]
+    fn into_pri(self) -> Primitive {
+        Primitive::None
+    }
+}
+
+impl IntoPrimitive for Primitive {
+    #[inline]
+    fn into_pri(self) -> Primitive {
+        self
+    }
+}
+
+impl<T: IntoPrimitive> IntoPrimitive for Vec<T> {
+    fn into_pri(self) -> Primitive {
+        Primitive::list(self.into_iter().map(IntoPrimitive::into_pri).collect())
+    }
+}
+
+impl<T: IntoPrimitive> IntoPrimitive for Option<T> {
+    fn into_pri(self) -> Primitive {
+        self.map_or(Primitive::None, IntoPrimitive::into_pri)
+    }
+}
+
+/// The error of a conversion that got a value of the wrong type
+fn mismatch(expected: &str, found: &Primitive) -> NolangError {
+    crate::error!(Type; "expected `{}`, found `{}`", expected, found.type_name())
+}
+
+impl FromPrimitive for Primitive {
+    #[inline]
+    fn from_pri(value: Primitive) -> Result<Self> {
+        Ok(value)
+    }
+}
+
+impl FromPrimitive for () {
+    // the value is ignored
+    #[inline]
+    fn from_pri(_: Primitive) -> Result<Self> {
+        Ok(())
+    }
+}
+
+impl FromPrimitive for bool {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        match value {
+            Primitive::Bool(b) => Ok(b),
+            e => Err(mismatch("Bool", &e)),
+        }
+    }
+}
+
+impl FromPrimitive for String {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        match value {
+            Primitive::Str(s) => Ok(s),
+            e => Err(mismatch("Str", &e)),
+        }
+    }
+}
+
+impl FromPrimitive for f64 {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        match value {
+            Primitive::Num(n) => Ok(n),
+            Primitive::Int(n) => Ok(n as f64),
+            e => Err(mismatch("Num", &e)),
+        }
+    }
+}
+
+impl FromPrimitive for i64 {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        match value {
+            Primitive::Int(n) => Ok(n),
+            e => Err(mismatch("Int", &e)),
+        }
+    }
+}
+
+impl FromPrimitive for BigInt {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        to_big_int(&value).ok_or_else(|| mismatch("BigInt", &value))
+    }
+}
+
+impl FromPrimitive for BigDecimal {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        to_decimal(&value).ok_or_else(|| mismatch("Decimal", &value))
+    }
+}
+
+impl<T: FromPrimitive> FromPrimitive for Vec<T> {
+    fn from_pri(value: Primitive) -> Result<Self> {
+        match value {
+            Primitive::List(list) => list.borrow().iter().cloned().map(T::from_pri).collect(),
+            e => Err(mismatch("List", &e)),
+        }
+    }
+}
+
+impl<T: FromPrimitive> FromPrimitive for Option<T> {
+    // `none` is `None`, any other value must convert to `T`
+    fn from_pri(value: Primitive) -> Result<Self> {
+        match value {
+            Primitive::None => Ok(None),
+            value => T::from_pri(value).map(Some),
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use super::builtins::native;
+use super::primitive::to_float;
+use super::{Arity, Primitive};
+use crate::error::Result;
+use bigdecimal::{RoundingMode, ToPrimitive};
+use std::collections::HashMap;
+
+/// The string argument of `name`
+fn string<'a>(name: &str, value: &'a Primitive) -> Result<&'a str> {
+    match value {
+        Primitive::Str(s) => Ok(s),
+        e => Err(crate::error!(Type; "`{}` expects a string, found {}", name, e)),
+    }
+}
+
+/// Add the string natives to the builtins. Positions in strings count characters, not bytes
+pub fn strings(current: &mut HashMap<String, Primitive>) {
+    native(current, "str", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Str(arguments[0].to_string()))
+    });
+
+    native(current, "num", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::Num(n) => Ok(Primitive::Num(*n)),
+        Primitive::Int(_) | Primitive::BigInt(_) | Primitive::Decimal(_) => {
+            Ok(Primitive::Num(to_float(&arguments[0]).unwrap_or(f64::NAN)))
+        }
+        Primitive::Str(s) => match s.trim().parse() {
+            Ok(n) => Ok(Primitive::Num(n)),
+            Err(_) => Err(crate::error!(Runtime; "can't convert {:?} to a number", s)),
+        },
+        e => Err(crate::error!(Type; "can't convert {} to a number", e)),
+    });
+
+    // numbers are truncated towards zero
+    native(current, "int", Arity::Fixed(1), |arguments| match &arguments[0] {
+        Primitive::Int(n) => Ok(Primitive::Int(*n)),
+        Primitive::Num(n) if n.is_finite() && n.trunc().abs() < 2f64.powi(63) => Ok(Primitive::Int(*n as i64)),
+        Primitive::Num(n) => Err(crate::error!(Runtime; "can't convert {} to an integer", n)),
+        Primitive::BigInt(n) => n
+            .to_i64()
+            .map(Primitive::Int)
+            .ok_or_else(|| crate::error!(Runtime; "{} doesn't fit in 64 bits", n)),
+        Primitive::Decimal(n) => n
+            .with_scale_round(0, RoundingMode::Down)
+            .to_i64()
+            .map(Primitive::Int)
+            .ok_or_else(|| crate::error!(Runtime; "{} doesn't fit in 64 bits", n)),
+        Primitive::Str(s) => match s.trim().parse() {
+            Ok(n) => Ok(Primitive::Int(n)),
+            Err(_) => Err(crate::error!(Runtime; "can't convert {:?} to an integer", s)),
+        },
+        e => Err(crate::error!(Type; "can't convert {} to an integer", e)),
+    });
+
+    native(current, "contains", Arity::Fixed(2), |arguments| match &arguments[0] {
+        Primitive::Str(s) => Ok(Primitive::Bool(s.contains(string("contains", &arguments[1])?))),
+        // values of different types are never equal
+        Primitive::List(list) => {
+            let found = list
+                .borrow()
+                .iter()
+                .any(|item| item.equals(&arguments[1]).unwrap_or(false));
+            Ok(Primitive::Bool(found))
+        }
+        e => Err(crate::error!(Type; "`contains` expects a string or a list, found {}", e)),
+    });
+
+    native(current, "find", Arity::Fixed(2), |arguments| {
+        let (s, pattern) = (string("find", &arguments[0])?, string("find", &arguments[1])?);
+        match s.find(pattern) {
+            Some(byte) => Ok(Primitive::Int(s[..byte].chars().count() as i64)),
+            None => Ok(Primitive::None),
+        }
+    });
+
+    native(current, "replace", Arity::Fixed(3), |arguments| {
+        let s = string("replace", &arguments[0])?;
+        let (from, to) = (string("replace", &arguments[1])?, string("replace", &arguments[2])?);
+        Ok(Primitive::Str(s.replace(from, to)))
+    });
+
+    native(current, "split", Arity::Range(1, 2), |arguments| {
+        // without a separator the string is split on whitespace, an empty one splits every character
+        let s = string("split", &arguments[0])?;
+        let parts: Vec<&str> = match arguments.get(1) {
+            None => s.split_whitespace().collect(),
+            Some(separator) => match string("split", separator)? {
+                "" => s.char_indices().map(|(i, c)| &s[i..i + c.len_utf8()]).collect(),
+                separator => s.split(separator).collect(),
+            },
+        };
+        Ok(Primitive::list(
+            parts.into_iter().map(|part| Primitive::Str(part.to_string())).collect(),
+        ))
+    });
+
+    native(current, "join", Arity::Range(1, 2), |arguments| {
+        let separator = match arguments.get(1) {
+            Some(separator) => string("join", separator)?,
+            None => "",
+        };
+        match &arguments[0] {
+            Primitive::List(list) => {
+                let items: Vec<String> = list.borrow().iter().map(|item| item.to_string()).collect();
+                Ok(Primitive::Str(items.join(separator)))
+            }
+            e => Err(crate::error!(Type; "`join` expects a list, found {}", e)),
+        }
+    });
+
+    native(current, "trim", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Str(string("trim", &arguments[0])?.trim().to_string()))
+    });
+
+    native(current, "upper", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Str(string("upper", &arguments[0])?.to_uppercase()))
+    });
+
+    native(current, "lower", Arity::Fixed(1), |arguments| {
+        Ok(Primitive::Str(string("lower", &arguments[0])?.to_lowercase()))
+    });
+
+    native(current, "starts_with", Arity::Fixed(2), |arguments| {
+        let (s, prefix) = (
+            string("starts_with", &arguments[0])?,
+            string("starts_with", &arguments[1])?,
+        );
+        Ok(Primitive::Bool(s.starts_with(prefix)))
+    });
+
+    native(current, "ends_with", Arity::Fixed(2), |arguments| {
+        let (s, suffix) = (string("ends_with", &arguments[0])?, string("ends_with", &arguments[1])?);
+        Ok(Primitive::Bool(s.ends_with(suffix)))
+    });
+}
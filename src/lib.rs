@@ -1,30 +1,31 @@
+pub mod checker;
+pub mod compiler;
+pub mod error;
 pub mod frontend;
 pub mod interpreter;
+pub mod modules;
+pub mod vm;
 
 mod util;
 
-pub use util::{colors::Colors, interpret_file::interpret_file, repl::repl};
+pub use bigdecimal::BigDecimal;
+pub use error::{ErrorKind, NolangError, TraceFrame};
+pub use interpreter::{Engine, FromPrimitive, IntoPrimitive, Primitive};
+#[cfg(feature = "derive")]
+pub use nolang_derive::{FromPrimitive, IntoPrimitive};
+pub use num_bigint::BigInt;
+pub use util::{
+    colors::Colors, diagnostic::DiagnosticRenderer, interpret_file::interpret_file, repl::repl,
+};
 
+/// Build a `NolangError` of the given kind, using `format!` for the message
 #[macro_export]
 macro_rules! error {
-    ($error_type:expr; $($format_args:expr),+ => $exit_value: expr) => {{
-        eprintln!(
-            "\x1b[1m{}\x1b[0m",
-            $crate::Colors::colorize(
-                $crate::Colors::Red,
-                &format!("├ {}:", $error_type)
-            )
-        );
-        eprint!(
-            "\x1b[1m{}\x1b[0m",
-            $crate::Colors::colorize(
-                $crate::Colors::Red,
-                "└─ "
-            )
-        );
-        eprintln!($($format_args),+);
+    ($kind:ident; $($format_args:expr),+) => {
+        $crate::NolangError::new($crate::ErrorKind::$kind, format!($($format_args),+))
+    };
 
-        std::panic::set_hook(Box::new(|_| {}));
-        panic!()
-    }}
+    ($kind:ident, $span:expr; $($format_args:expr),+) => {
+        $crate::error!($kind; $($format_args),+).at($span)
+    };
 }
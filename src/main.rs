@@ -10,7 +10,7 @@ fn main() -> io::Result<()> {
     if args.iter().filter(|x| !x.starts_with('-')).count() < 2 {
         repl(flairs.as_slice())?;
     } else {
-        interpret_file()?;
+        interpret_file(flairs.as_slice())?;
     }
     Ok(())
 }
@@ -0,0 +1,134 @@
+use crate::checker::check;
+use crate::error::{NolangError, Result};
+use crate::frontend::{parse, Span, Statement, StatementKind};
+use crate::interpreter::Primitive;
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// A file that was imported, with the values it exported
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub exports: HashMap<String, Primitive>,
+}
+
+impl Module {
+    /// Get an exported value, used by `module.name` and by `import name from "module"`
+    pub fn get(&self, name: &str) -> Result<Primitive> {
+        match self.exports.get(name) {
+            Some(value) => Ok(value.clone()),
+            None => Err(crate::error!(Reference; "module `{}` has no export named `{}`", self.name, name)),
+        }
+    }
+}
+
+/// What the runtime has to do to import a module
+pub enum Import {
+    Loaded(Rc<Module>),
+    Run(PathBuf, Vec<Statement>), // the module wasn't run yet, `ModuleLoader::finish` must be called after running it
+}
+
+/// Finds, parses and caches the modules of a program, so each file is run only once. The
+/// interpreter and the vm run the modules themselves, between `start` and `finish`
+pub struct ModuleLoader {
+    cache: HashMap<PathBuf, Rc<Module>>,
+    loading: Vec<PathBuf>, // modules being run, to detect circular imports
+    type_check: bool,      // false with `--no-check`, like the file being run
+}
+
+impl Default for ModuleLoader {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl ModuleLoader {
+    /// A loader that type checks the modules before they're run when `type_check` is set
+    pub fn new(type_check: bool) -> Self {
+        Self {
+            cache: HashMap::new(),
+            loading: Vec::new(),
+            type_check,
+        }
+    }
+
+    /// Resolve `path` relative to the file of `span` and get the module if it was already run,
+    /// or its statements if it must be run now
+    pub fn start(&mut self, path: &str, span: &Span) -> Result<Import> {
+        let mut file = Path::new(&*span.file).parent().unwrap_or_else(|| Path::new("")).join(path);
+        if file.extension().is_none() {
+            file.set_extension("no");
+        }
+
+        let key = file
+            .canonicalize()
+            .map_err(|e| crate::error!(Import; "can't find module `{}` ({}): {}", path, file.display(), e))?;
+
+        // the first import comes from the file being run, which can be imported back as well
+        if self.loading.is_empty() {
+            if let Ok(main) = Path::new(&*span.file).canonicalize() {
+                self.loading.push(main);
+            }
+        }
+
+        if let Some(module) = self.cache.get(&key) {
+            return Ok(Import::Loaded(module.clone()));
+        }
+
+        if let Some(index) = self.loading.iter().position(|p| *p == key) {
+            let mut cycle: Vec<String> = self.loading[index..].iter().map(|p| module_name(p)).collect();
+            cycle.push(module_name(&key));
+            return Err(crate::error!(Import; "circular import: {}", cycle.join(" -> ")));
+        }
+
+        let source = read_to_string(&key).map_err(|e| crate::error!(Import; "can't read module `{}`: {}", path, e))?;
+        let program: Vec<Statement> = match parse(source, &file.to_string_lossy()) {
+            Ok(program) => program.collect(),
+            Err(errors) => return Err(NolangError::from_many(errors)),
+        };
+        if self.type_check {
+            let errors = check(&program);
+            if !errors.is_empty() {
+                return Err(NolangError::from_many(errors));
+            }
+        }
+
+        self.loading.push(key.clone());
+        Ok(Import::Run(key, program))
+    }
+
+    /// Save the exports of the module that was run, so later imports reuse them
+    pub fn finish(&mut self, key: PathBuf, exports: Result<HashMap<String, Primitive>>) -> Result<Rc<Module>> {
+        self.loading.retain(|p| *p != key);
+
+        let module = Rc::new(Module {
+            name: module_name(&key),
+            exports: exports?,
+        });
+        self.cache.insert(key, module.clone());
+        Ok(module)
+    }
+}
+
+/// The name a module is bound to by `import "path"`, which is its file name
+pub fn module_name(path: &Path) -> String {
+    path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Names of the top level bindings marked with `export`
+pub fn exported_names(statements: &[Statement]) -> Vec<String> {
+    statements
+        .iter()
+        .filter_map(|statement| match statement.kind {
+            StatementKind::Export(ref exported) => match exported.kind {
+                StatementKind::Assign(ref name, ..) | StatementKind::FuncAssign(ref name, ..) => Some(name.to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
@@ -25,4 +25,16 @@ impl Colors {
 
         format!("\x1b[{}m{}\x1b[0m", color_code, args)
     }
+
+    pub fn bold(args: &str) -> String {
+        format!("\x1b[1m{}\x1b[0m", args)
+    }
+
+    /// Colorize and make the text bold, only when `enabled` is true
+    pub fn paint(color: Colors, args: &str, enabled: bool) -> String {
+        match enabled {
+            true => Self::bold(&Self::colorize(color, args)),
+            false => args.to_string(),
+        }
+    }
 }
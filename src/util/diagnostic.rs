@@ -0,0 +1,234 @@
+use crate::{
+    error::{Note, TraceFrame},
+    frontend::Span,
+    util::colors::Colors,
+    NolangError,
+};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs::read_to_string,
+    io::{stderr, IsTerminal},
+};
+
+/// A span that should be underlined in a snippet
+struct Label<'a> {
+    span: &'a Span,
+    message: Option<&'a str>,
+    primary: bool,
+}
+
+/// Renders errors like rustc does: the error kind and message, followed by the source lines it
+/// points to with the exact span underlined and the secondary notes next to it
+pub struct DiagnosticRenderer {
+    sources: HashMap<String, String>,
+    color: bool,
+}
+
+impl Default for DiagnosticRenderer {
+    fn default() -> Self {
+        Self {
+            sources: HashMap::new(),
+            color: stderr().is_terminal(),
+        }
+    }
+}
+
+impl DiagnosticRenderer {
+    /// Force colors on or off, by default they're only used when stderr is a terminal
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Register the source code of `file`, so spans in it can be shown
+    pub fn add_source(&mut self, file: &str, source: &str) {
+        self.sources.insert(file.to_string(), source.to_string());
+    }
+
+    /// Print every error to stderr
+    pub fn emit(&self, errors: &[NolangError]) {
+        for error in errors {
+            eprintln!("{}", self.render(error));
+        }
+
+        if errors.len() > 1 {
+            eprintln!(
+                "{}: aborting due to {} previous errors\n",
+                self.paint(Colors::Red, "error"),
+                errors.len()
+            );
+        }
+    }
+
+    pub fn render(&self, error: &NolangError) -> String {
+        let mut out = format!(
+            "{}{}\n",
+            self.paint(Colors::Red, &format!("error[{}]", error.kind)),
+            Colors::paint(Colors::White, &format!(": {}", error.message), self.color)
+        );
+
+        let (same_file, other): (Vec<&Note>, Vec<&Note>) = error.notes.iter().partition(|note| {
+            matches!((&note.span, &error.span), (Some(n), Some(e)) if n.file == e.file)
+        });
+
+        if let Some(ref span) = error.span {
+            let mut labels = vec![Label {
+                span,
+                message: None,
+                primary: true,
+            }];
+            labels.extend(same_file.iter().map(|note| Label {
+                span: note.span.as_ref().unwrap(),
+                message: Some(&note.message),
+                primary: false,
+            }));
+            self.snippet(&mut out, span, labels);
+        }
+
+        for note in other {
+            match note.span {
+                Some(ref span) => {
+                    writeln!(out, "{}: {}", self.paint(Colors::LightBlue, "note"), note.message).unwrap();
+                    let label = Label {
+                        span,
+                        message: None,
+                        primary: false,
+                    };
+                    self.snippet(&mut out, span, vec![label]);
+                }
+                None => {
+                    writeln!(out, "  {} {}: {}", self.paint(Colors::LightBlue, "="), self.bold("note"), note.message)
+                        .unwrap()
+                }
+            }
+        }
+
+        self.trace(&mut out, &error.trace);
+        out
+    }
+
+    /// Write the calls that were running, innermost first. Runs of the same call, like the ones
+    /// left by a deep recursion, are written once
+    fn trace(&self, out: &mut String, trace: &[TraceFrame]) {
+        if trace.is_empty() {
+            return;
+        }
+        writeln!(out, "{}", self.paint(Colors::LightBlue, "traceback, innermost call first:")).unwrap();
+
+        let mut index = 0;
+        while index < trace.len() {
+            let frame = &trace[index];
+            let repeated = trace[index..].iter().take_while(|other| *other == frame).count();
+            writeln!(out, "  in `{}`, called at {}", frame.function, frame.span).unwrap();
+            if repeated > 1 {
+                writeln!(out, "  ... the call above is repeated {} more times", repeated - 1).unwrap();
+            }
+            index += repeated;
+        }
+    }
+
+    fn paint(&self, color: Colors, text: &str) -> String {
+        Colors::paint(color, text, self.color)
+    }
+
+    fn bold(&self, text: &str) -> String {
+        match self.color {
+            true => Colors::bold(text),
+            false => text.to_string(),
+        }
+    }
+
+    /// Write the ` --> file:line:col` header and, when the source is known, the underlined lines
+    fn snippet(&self, out: &mut String, location: &Span, mut labels: Vec<Label>) {
+        // errors in imported modules point to files that weren't registered, so they're read here
+        let read;
+        let source = match self.sources.get(&*location.file) {
+            Some(source) => Some(source.as_str()),
+            None => {
+                read = read_to_string(&*location.file).ok();
+                read.as_deref()
+            }
+        };
+        let lines: Vec<&str> = source.map(|s| s.split('\n').collect()).unwrap_or_default();
+
+        labels.retain(|l| l.span.line >= 1 && l.span.line <= lines.len());
+        labels.sort_by_key(|l| (l.span.line, l.span.column));
+
+        let max_line = labels.iter().map(|l| l.span.line).max().unwrap_or(location.line);
+        let width = max_line.to_string().len();
+        let gutter = |text: &str| format!("{} {} ", " ".repeat(width - text.len()) + text, "|");
+
+        writeln!(out, "{}{} {}", " ".repeat(width), self.paint(Colors::Blue, "-->"), location).unwrap();
+        if labels.is_empty() {
+            return;
+        }
+        writeln!(out, "{}", self.paint(Colors::Blue, gutter("").trim_end())).unwrap();
+
+        // byte offset where each line starts, to know which part of the line a span covers
+        let mut line_starts = vec![0];
+        let mut offset = 0;
+        for line in &lines {
+            offset += line.len() + 1;
+            line_starts.push(offset);
+        }
+
+        let mut shown_lines: Vec<usize> = labels.iter().map(|l| l.span.line).collect();
+        shown_lines.dedup();
+
+        let mut last_line: Option<usize> = None;
+        for line in shown_lines {
+            match last_line {
+                Some(last) if line == last + 2 => self.source_line(out, &gutter, last + 1, lines[last]),
+                Some(last) if line > last + 2 => writeln!(out, "{}", self.paint(Colors::Blue, "...")).unwrap(),
+                _ => (),
+            }
+            self.source_line(out, &gutter, line, lines[line - 1]);
+
+            let text = lines[line - 1];
+            let line_start = line_starts[line - 1];
+            for label in labels.iter().filter(|l| l.span.line == line) {
+                let start = label.span.start.saturating_sub(line_start).min(text.len());
+                let end = label.span.end.saturating_sub(line_start).clamp(start, text.len());
+
+                let padding = display_width(text.get(..start).unwrap_or(""));
+                let length = display_width(text.get(start..end).unwrap_or("")).max(1);
+
+                let (mark, color) = match label.primary {
+                    true => ("^", Colors::Red),
+                    false => ("-", Colors::LightBlue),
+                };
+                let mut underline = mark.repeat(length);
+                if let Some(message) = label.message {
+                    underline = format!("{} {}", underline, message);
+                }
+
+                writeln!(
+                    out,
+                    "{}{}{}",
+                    self.paint(Colors::Blue, &gutter("")),
+                    " ".repeat(padding),
+                    self.paint(color, &underline)
+                )
+                .unwrap();
+            }
+            last_line = Some(line);
+        }
+        writeln!(out, "{}", self.paint(Colors::Blue, gutter("").trim_end())).unwrap();
+    }
+
+    fn source_line(&self, out: &mut String, gutter: &dyn Fn(&str) -> String, line: usize, text: &str) {
+        writeln!(
+            out,
+            "{}{}",
+            self.paint(Colors::Blue, &gutter(&line.to_string())),
+            text.replace('\t', "    ").trim_end_matches('\r')
+        )
+        .unwrap();
+    }
+}
+
+/// How many columns the text takes when printed, tabs are shown as four spaces
+fn display_width(text: &str) -> usize {
+    text.chars().map(|c| if c == '\t' { 4 } else { 1 }).sum()
+}
@@ -1,12 +1,46 @@
-use crate::{frontend::parse, interpreter::interpret};
-use std::{env::args, fs::read_to_string, io};
+use crate::{checker::check, frontend::parse, interpreter::interpret, util::max_depth, vm, DiagnosticRenderer};
+use std::{fs::read_to_string, io, process::exit};
 
-pub fn interpret_file() -> io::Result<()> {
-    let mut arguments = args();
-    arguments.next();
+/// Type check and run every file passed in the arguments. `--vm` runs them with the bytecode vm
+/// instead of the tree-walker, `--no-check` skips the type checker and `--max-depth=N` changes how
+/// deep the recursion can go
+pub fn interpret_file(arguments: &[&str]) -> io::Result<()> {
+    let use_vm = arguments.contains(&"--vm");
+    let type_check = !arguments.contains(&"--no-check");
+    let max_depth = max_depth(arguments);
 
-    for file in arguments {
-        interpret(parse(read_to_string(file)?), None);
+    let mut renderer = DiagnosticRenderer::default();
+
+    for file in arguments.iter().skip(1).filter(|arg| !arg.starts_with('-')) {
+        let source = read_to_string(file)?;
+        renderer.add_source(file, &source);
+
+        let errors = match parse(source, file) {
+            Ok(program) => {
+                let program: Vec<_> = program.collect();
+                let errors = match type_check {
+                    true => check(&program),
+                    false => vec![],
+                };
+                if !errors.is_empty() {
+                    renderer.emit(&errors);
+                    exit(1);
+                }
+
+                let result = match use_vm {
+                    true => vm::run(program.into_iter(), max_depth, type_check),
+                    false => interpret(program.into_iter(), None, max_depth, type_check),
+                };
+                match result {
+                    Ok(_) => continue,
+                    Err(e) => vec![e],
+                }
+            }
+            Err(errors) => errors,
+        };
+
+        renderer.emit(&errors);
+        exit(1);
     }
     Ok(())
 }
@@ -1,3 +1,23 @@
 pub mod colors;
+pub mod diagnostic;
 pub mod interpret_file;
 pub mod repl;
+
+use crate::interpreter::DEFAULT_MAX_DEPTH;
+use std::process::exit;
+
+/// The limit of nolang calls running at once given with `--max-depth=N`
+pub fn max_depth(arguments: &[&str]) -> usize {
+    let value = match arguments.iter().find_map(|arg| arg.strip_prefix("--max-depth=")) {
+        Some(value) => value,
+        None => return DEFAULT_MAX_DEPTH,
+    };
+
+    match value.parse() {
+        Ok(depth) => depth,
+        Err(_) => {
+            eprintln!("`--max-depth` expects a number, found `{}`", value);
+            exit(1)
+        }
+    }
+}
@@ -1,17 +1,19 @@
 use std::{
     env::var,
     io::{self, stdin, stdout, Write},
-    panic::catch_unwind,
-    sync::Mutex,
 };
 
 use crate::{
-    frontend::{lex, parse},
+    checker::Checker,
+    compiler::compile,
+    frontend::{lex, parse, parse_partial, Statement},
     interpreter::InterpreterDebug,
-    util::colors::Colors,
+    util::{colors::Colors, diagnostic::DiagnosticRenderer, max_depth},
+    vm::Vm,
 };
 
-fn print_read() -> io::Result<String> {
+/// Print the prompt and read one line, returns `None` on EOF
+fn print_read() -> io::Result<Option<String>> {
     use Colors::*;
 
     print!(
@@ -24,39 +26,94 @@ fn print_read() -> io::Result<String> {
     stdout().flush()?;
 
     let mut input = String::new();
-    stdin().read_line(&mut input)?;
-    Ok(input)
+    match stdin().read_line(&mut input)? {
+        0 => Ok(None),
+        _ => Ok(Some(input)),
+    }
 }
 
-pub fn repl(arguments: &[&str]) -> io::Result<()> {
-    match arguments.get(1) {
-        Some(&"-p") => loop {
-            let input = print_read()?;
-            println!("{:#?}", parse(input).collect::<Vec<_>>());
-        },
+/// Show the errors of one line of input
+fn report(input: &str, errors: &[crate::NolangError]) {
+    let mut renderer = DiagnosticRenderer::default();
+    renderer.add_source("<repl>", input);
+    renderer.emit(errors);
+}
 
-        Some(&"-l") => loop {
-            let input = print_read()?;
-            println!("{:#?}", lex(input).collect::<Vec<_>>())
-        },
+/// Parse and type check one line of input, reporting the errors if there are any
+fn parse_checked(input: &str, checker: &mut Checker) -> Option<Vec<Statement>> {
+    let errors = match parse(input.to_string(), "<repl>") {
+        Ok(program) => {
+            let program: Vec<Statement> = program.collect();
+            let errors = checker.check(&program);
+            if errors.is_empty() {
+                return Some(program);
+            }
+            errors
+        }
+        Err(errors) => errors,
+    };
+    report(input, &errors);
+    None
+}
 
-        Some(e) => panic!("Unrecognized option `{}`", e),
+pub fn repl(arguments: &[&str]) -> io::Result<()> {
+    let max_depth = max_depth(arguments);
 
-        None => loop {
-            let runtime = Mutex::new(InterpreterDebug::default());
+    match arguments.iter().skip(1).find(|arg| !arg.starts_with("--max-depth=")) {
+        Some(&"-p") => {
+            while let Some(input) = print_read()? {
+                let (statements, errors) = parse_partial(input.clone(), "<repl>");
+                println!("{:#?}", statements);
+                report(&input, &errors);
+            }
+        }
 
-            while !runtime.is_poisoned() {
-                let input = print_read()?;
+        Some(&"-l") => {
+            while let Some(input) = print_read()? {
+                match lex(input.clone(), "<repl>") {
+                    Ok(tokens) => println!("{:#?}", tokens.collect::<Vec<_>>()),
+                    Err(e) => report(&input, &[e]),
+                }
+            }
+        }
+
+        Some(&"--vm") => {
+            let mut vm = Vm::with_max_depth(max_depth);
+            let mut checker = Checker::default();
 
+            while let Some(input) = print_read()? {
                 if input.trim() != "" {
-                    drop(catch_unwind(|| {
-                        let mut runtime = runtime.lock().unwrap();
-                        runtime.interpret_debug(parse(input.to_string()))
-                    }));
+                    if let Some(program) = parse_checked(&input, &mut checker) {
+                        for statement in program {
+                            match compile(std::iter::once(statement)).and_then(|f| vm.run(f)) {
+                                Ok(value) => println!("=> {}", value),
+                                Err(e) => {
+                                    report(&input, &[e]);
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
 
-                    drop(std::panic::take_hook());
+        Some(e) => eprintln!("Unrecognized option `{}`", e),
+
+        None => {
+            let mut runtime = InterpreterDebug::with_max_depth(max_depth);
+            let mut checker = Checker::default();
+
+            while let Some(input) = print_read()? {
+                if input.trim() != "" {
+                    if let Some(program) = parse_checked(&input, &mut checker) {
+                        if let Err(e) = runtime.interpret_debug(program.into_iter()) {
+                            report(&input, &[e]);
+                        }
+                    }
                 }
             }
-        },
+        }
     }
+    Ok(())
 }
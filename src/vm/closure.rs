@@ -0,0 +1,27 @@
+use crate::{compiler::Function, interpreter::Primitive};
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+/// A variable captured by a closure. It points to the stack while the variable's scope is alive,
+/// and holds the value itself after that
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize), // stack index
+    Closed(Primitive),
+}
+
+/// The global variables of a program or of a module. Functions keep the globals of the file they
+/// were defined in, so exported functions still find them once the module's vm is gone
+pub type Globals = Rc<RefCell<HashMap<String, Primitive>>>;
+
+/// A compiled function, the variables it captured when it was made and the globals it uses
+pub struct Closure {
+    pub function: Rc<Function>,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    pub globals: Globals,
+}
+
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.function)
+    }
+}
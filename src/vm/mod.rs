@@ -0,0 +1,488 @@
+mod closure;
+
+pub use closure::{Closure, Globals, Upvalue};
+
+use crate::compiler::{compile, Function, Instruction};
+use crate::error::{Result, TraceFrame};
+use crate::frontend::{Span, Statement};
+use crate::interpreter::{builtins, Context, IntoPrimitive, Primitive, DEFAULT_MAX_DEPTH};
+use crate::modules::{exported_names, Import, Module, ModuleLoader};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::BTreeMap,
+    rc::Rc,
+};
+
+/// A function being run, `base` is the stack index of its slot 0
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base: usize,
+}
+
+/// Stack based virtual machine that runs the bytecode made by the compiler
+pub struct Vm {
+    stack: Vec<Primitive>,
+    frames: Vec<Frame>,
+    globals: Globals,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>, // upvalues still on the stack, sorted by stack index
+    modules: Rc<RefCell<ModuleLoader>>,
+    max_depth: usize, // how many nolang calls can be running at once
+    context: Rc<Context>, // shared with the vms of the modules
+}
+
+impl Default for Vm {
+    fn default() -> Self {
+        Self::with_max_depth(DEFAULT_MAX_DEPTH)
+    }
+}
+
+/// Compile the statements and run them in a new vm, returning the value of the last one. The
+/// imported modules are type checked when `type_check` is set
+pub fn run(statements: impl Iterator<Item = Statement>, max_depth: usize, type_check: bool) -> Result<Primitive> {
+    let mut vm = Vm {
+        modules: Rc::new(RefCell::new(ModuleLoader::new(type_check))),
+        ..Vm::with_max_depth(max_depth)
+    };
+    vm.run(compile(statements)?)
+}
+
+impl Vm {
+    pub fn with_max_depth(max_depth: usize) -> Self {
+        Self {
+            stack: Vec::new(),
+            frames: Vec::new(),
+            globals: Rc::new(RefCell::new(builtins())),
+            open_upvalues: Vec::new(),
+            modules: Default::default(),
+            max_depth,
+            context: Default::default(),
+        }
+    }
+
+    /// Run a compiled program. Globals are kept between runs
+    pub fn run(&mut self, function: Rc<Function>) -> Result<Primitive> {
+        let base = self.stack.len();
+        let depth = self.frames.len();
+
+        let closure = Rc::new(Closure {
+            function,
+            upvalues: Vec::new(),
+            globals: self.globals.clone(),
+        });
+        self.stack.push(Primitive::Compiled(closure.clone()));
+        self.frames.push(Frame { closure, ip: 0, base });
+
+        let context = self.context.clone();
+        let result = context.enter(|| self.execute(depth));
+        if result.is_err() {
+            self.frames.truncate(depth);
+            self.truncate(base);
+        }
+        result
+    }
+
+    /// Run until the frame at `depth` returns
+    fn execute(&mut self, depth: usize) -> Result<Primitive> {
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let instruction = frame.closure.function.chunk.code[frame.ip];
+            frame.ip += 1;
+
+            if let Instruction::Return = instruction {
+                let result = self.pop();
+                let frame = self.frames.pop().unwrap();
+                self.truncate(frame.base);
+
+                if self.frames.len() == depth {
+                    return Ok(result);
+                }
+                self.stack.push(result);
+                continue;
+            }
+
+            if let Err(e) = self.instruction(instruction) {
+                let frame = self.frames.last().unwrap();
+                let error = e.or_at(&frame.closure.function.chunk.spans[frame.ip - 1]);
+                return Err(match error.trace.is_empty() {
+                    true => error.with_trace(self.trace()),
+                    false => error,
+                });
+            }
+        }
+    }
+
+    fn instruction(&mut self, instruction: Instruction) -> Result<()> {
+        use Instruction::*;
+
+        match instruction {
+            Constant(index) => {
+                let value = self.constant(index);
+                self.stack.push(value)
+            }
+            Pop => {
+                self.pop();
+            }
+            Drop(values) => self.truncate(self.stack.len() - values),
+
+            GetLocal(slot) => {
+                let value = self.stack[self.base() + slot].clone();
+                self.stack.push(value)
+            }
+            SetLocal(slot) => {
+                let index = self.base() + slot;
+                self.stack[index] = self.peek().clone();
+            }
+            GetUpvalue(index) => {
+                let upvalue = self.frames.last().unwrap().closure.upvalues[index].clone();
+                let value = match *upvalue.borrow() {
+                    Upvalue::Open(slot) => self.stack[slot].clone(),
+                    Upvalue::Closed(ref value) => value.clone(),
+                };
+                self.stack.push(value)
+            }
+            SetUpvalue(index) => {
+                let upvalue = self.frames.last().unwrap().closure.upvalues[index].clone();
+                let value = self.peek().clone();
+                match *upvalue.borrow_mut() {
+                    Upvalue::Open(slot) => self.stack[slot] = value,
+                    Upvalue::Closed(ref mut closed) => *closed = value,
+                };
+            }
+            GetGlobal(name) => {
+                let name = self.constant_name(name);
+                let value = global(self.globals(), &name)?;
+                self.stack.push(value)
+            }
+            SetGlobal(name) => {
+                let name = self.constant_name(name);
+                let value = self.pop();
+                self.globals().borrow_mut().insert(name, value);
+            }
+            AssignGlobal(name) => {
+                let name = self.constant_name(name);
+                let value = self.peek().clone();
+                match self.globals().borrow_mut().get_mut(&name) {
+                    Some(global) => *global = value,
+                    None => return Err(crate::error!(Reference; "assigning to undeclared variable {}", name)),
+                }
+            }
+            Import(path) => {
+                let path = self.constant_name(path);
+                let frame = self.frames.last().unwrap();
+                let span = frame.closure.function.chunk.spans[frame.ip - 1].clone();
+                let module = self.import(&path, &span)?;
+                self.stack.push(Primitive::Module(module))
+            }
+            GetField(name) => {
+                let name = self.constant_name(name);
+                let value = self.pop().field(&name)?;
+                self.stack.push(value)
+            }
+
+            Add => self.binary(|l, r| l + r)?,
+            Sub => self.binary(|l, r| l - r)?,
+            Mul => self.binary(|l, r| l * r)?,
+            Div => self.binary(|l, r| l / r)?,
+            FloorDiv => self.binary(|l, r| l.floor_div(r))?,
+            Rem => self.binary(|l, r| l % r)?,
+            Pow => self.binary(|l, r| l.pow(r))?,
+            Neg => {
+                let value = (-self.pop())?;
+                self.stack.push(value)
+            }
+            Not => {
+                let value = !self.pop();
+                self.stack.push(Primitive::Bool(value))
+            }
+
+            Equal => self.binary(|l, r| Ok(l.equals(&r)?.into_pri()))?,
+            NotEqual => self.binary(|l, r| Ok((!l.equals(&r)?).into_pri()))?,
+            Greater => self.compare(|o| o == Ordering::Greater)?,
+            GreaterEqual => self.compare(|o| o != Ordering::Less)?,
+            Less => self.compare(|o| o == Ordering::Less)?,
+            LessEqual => self.compare(|o| o != Ordering::Greater)?,
+
+            Jump(target) => self.jump(target),
+            JumpIfFalse(target) => {
+                if !self.peek().to_bool() {
+                    self.jump(target)
+                }
+            }
+            JumpIfTrue(target) => {
+                if self.peek().to_bool() {
+                    self.jump(target)
+                }
+            }
+
+            Iter => {
+                let items = self.pop().items()?;
+                self.stack.push(Primitive::list(items))
+            }
+            ForNext(target) => {
+                let top = self.stack.len() - 1;
+                let position = match self.stack[top] {
+                    Primitive::Int(position) => position as usize,
+                    _ => unreachable!(),
+                };
+                let item = match self.stack[top - 1] {
+                    Primitive::List(ref items) => items.borrow().get(position).cloned(),
+                    _ => unreachable!(),
+                };
+
+                match item {
+                    Some(item) => {
+                        self.stack[top] = Primitive::Int(position as i64 + 1);
+                        self.stack.push(item)
+                    }
+                    None => self.jump(target),
+                }
+            }
+
+            List(items) => {
+                let items = self.stack.split_off(self.stack.len() - items);
+                self.stack.push(Primitive::list(items))
+            }
+            Template(parts) => {
+                let parts = self.stack.split_off(self.stack.len() - parts);
+                let joined: String = parts.iter().map(|part| part.to_string()).collect();
+                self.stack.push(Primitive::Str(joined))
+            }
+            Map(entries) => {
+                let values = self.stack.split_off(self.stack.len() - entries * 2);
+                let mut map = BTreeMap::new();
+                for pair in values.chunks(2) {
+                    if let Primitive::Str(ref key) = pair[0] {
+                        map.insert(key.to_string(), pair[1].clone());
+                    }
+                }
+                self.stack.push(Primitive::map(map))
+            }
+            Index => self.binary(|value, index| value.index(&index))?,
+            Slice => {
+                let end = self.pop();
+                let start = self.pop();
+                let value = self.pop().slice(&start, &end)?;
+                self.stack.push(value)
+            }
+            Closure(index) => {
+                let frame = self.frames.last().unwrap();
+                let (enclosing, base) = (frame.closure.clone(), frame.base);
+                let function = enclosing.function.chunk.functions[index].clone();
+
+                let upvalues = function
+                    .upvalues
+                    .iter()
+                    .map(|capture| match capture.is_local {
+                        true => self.capture(base + capture.index),
+                        false => enclosing.upvalues[capture.index].clone(),
+                    })
+                    .collect();
+                let globals = enclosing.globals.clone();
+                self.stack.push(Primitive::Compiled(Rc::new(closure::Closure {
+                    function,
+                    upvalues,
+                    globals,
+                })))
+            }
+            Call(arguments) => self.call(arguments)?,
+            TailCall(arguments) => self.tail_call(arguments)?,
+            EndScope(locals) => {
+                let value = self.pop();
+                self.truncate(self.stack.len() - locals);
+                self.stack.push(value)
+            }
+            Return => unreachable!(), // handled by `execute`, since it changes the running frame
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, arguments: usize) -> Result<()> {
+        let called = self.stack.len() - arguments - 1;
+
+        match self.stack[called].clone() {
+            Primitive::Compiled(closure) => {
+                let params = closure.function.params.len();
+                if arguments < params {
+                    return Err(crate::error!(Call; "Missing arguments for function call"));
+                }
+
+                // the first frame runs the script
+                if self.frames.len() > self.max_depth {
+                    let error = crate::error!(StackOverflow; "maximum recursion depth of {} exceeded", self.max_depth);
+                    return Err(error.with_trace(self.trace()));
+                }
+
+                // extra arguments are ignored, like in the tree-walker
+                self.stack.truncate(called + params + 1);
+
+                self.frames.push(Frame {
+                    closure,
+                    ip: 0,
+                    base: called,
+                });
+            }
+            Primitive::NativeFunc(native) => {
+                let arguments = self.stack.split_off(called + 1);
+                self.pop();
+                self.stack.push(native.call(&arguments)?)
+            }
+            e => return Err(crate::error!(Call; "can't call `{}`", e)),
+        }
+        Ok(())
+    }
+
+    /// Call a nolang function by replacing the running frame, other values are called normally
+    fn tail_call(&mut self, arguments: usize) -> Result<()> {
+        let called = self.stack.len() - arguments - 1;
+
+        let closure = match self.stack[called] {
+            Primitive::Compiled(ref closure) => closure.clone(),
+            _ => return self.call(arguments),
+        };
+        let params = closure.function.params.len();
+        if arguments < params {
+            return Err(crate::error!(Call; "Missing arguments for function call"));
+        }
+        self.stack.truncate(called + params + 1);
+
+        // move the function and its arguments to the start of the frame, closing its locals
+        let base = self.base();
+        let values = self.stack.split_off(called);
+        self.truncate(base);
+        self.stack.extend(values);
+
+        let frame = self.frames.last_mut().unwrap();
+        frame.closure = closure;
+        frame.ip = 0;
+        Ok(())
+    }
+
+    /// The calls being run, innermost first, with where each one was made
+    fn trace(&self) -> Vec<TraceFrame> {
+        self.frames
+            .windows(2)
+            .rev()
+            .map(|pair| {
+                let (caller, called) = (&pair[0], &pair[1]);
+                TraceFrame {
+                    function: called.closure.function.name.to_string(),
+                    span: caller.closure.function.chunk.spans[caller.ip - 1].clone(),
+                }
+            })
+            .collect()
+    }
+
+    /// Get a module from the cache, or run it in a new vm and save its exports
+    fn import(&mut self, path: &str, span: &Span) -> Result<Rc<Module>> {
+        let import = self.modules.borrow_mut().start(path, span)?;
+        let (key, program) = match import {
+            Import::Loaded(module) => return Ok(module),
+            Import::Run(key, program) => (key, program),
+        };
+
+        let names = exported_names(&program);
+        let mut vm = Vm {
+            modules: self.modules.clone(),
+            context: self.context.clone(),
+            ..Vm::with_max_depth(self.max_depth)
+        };
+        let exports = compile(program.into_iter())
+            .and_then(|function| vm.run(function))
+            .and_then(|_| names.into_iter().map(|name| Ok((name.to_string(), global(&vm.globals, &name)?))).collect());
+
+        self.modules
+            .borrow_mut()
+            .finish(key, exports)
+            .map_err(|e| e.with_note("imported here", Some(span.clone())))
+    }
+
+    fn binary(&mut self, operation: fn(Primitive, Primitive) -> Result<Primitive>) -> Result<()> {
+        let right = self.pop();
+        let left = self.pop();
+        self.stack.push(operation(left, right)?);
+        Ok(())
+    }
+
+    fn compare(&mut self, check: fn(Ordering) -> bool) -> Result<()> {
+        let right = self.pop();
+        let left = self.pop();
+        let result = left.compare(&right)?.map(check).unwrap_or(false);
+        self.stack.push(Primitive::Bool(result));
+        Ok(())
+    }
+
+    /// The globals of the file the running function was defined in
+    fn globals(&self) -> &Globals {
+        &self.frames.last().unwrap().closure.globals
+    }
+
+    /// Get the upvalue pointing to the stack index `slot`, so closures capturing the same
+    /// variable share it
+    fn capture(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let position = self
+            .open_upvalues
+            .iter()
+            .position(|u| matches!(*u.borrow(), Upvalue::Open(s) if s >= slot))
+            .unwrap_or(self.open_upvalues.len());
+
+        if let Some(upvalue) = self.open_upvalues.get(position) {
+            if matches!(*upvalue.borrow(), Upvalue::Open(s) if s == slot) {
+                return upvalue.clone();
+            }
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.insert(position, upvalue.clone());
+        upvalue
+    }
+
+    /// Shrink the stack to `length` values, moving the variables captured from the removed
+    /// values into their upvalues
+    fn truncate(&mut self, length: usize) {
+        while let Some(upvalue) = self.open_upvalues.last() {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) if slot >= length => slot,
+                _ => break,
+            };
+            *upvalue.borrow_mut() = Upvalue::Closed(self.stack[slot].clone());
+            self.open_upvalues.pop();
+        }
+        self.stack.truncate(length);
+    }
+
+    fn jump(&mut self, target: usize) {
+        self.frames.last_mut().unwrap().ip = target
+    }
+
+    fn base(&self) -> usize {
+        self.frames.last().unwrap().base
+    }
+
+    fn constant(&self, index: usize) -> Primitive {
+        self.frames.last().unwrap().closure.function.chunk.constants[index].clone()
+    }
+
+    fn constant_name(&self, index: usize) -> String {
+        match self.constant(index) {
+            Primitive::Str(name) => name,
+            _ => unreachable!(),
+        }
+    }
+
+    fn pop(&mut self) -> Primitive {
+        self.stack.pop().expect("vm stack underflow")
+    }
+
+    fn peek(&self) -> &Primitive {
+        self.stack.last().expect("vm stack underflow")
+    }
+}
+
+fn global(globals: &Globals, name: &str) -> Result<Primitive> {
+    match globals.borrow().get(name) {
+        Some(value) => Ok(value.clone()),
+        None => Err(crate::error!(Reference; "acessing undefined variable {}", name)),
+    }
+}
@@ -0,0 +1,174 @@
+//! Runs nolang scripts with the tree-walker and with the vm, which must print the same output
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// The `tests/scripts` directory, the scripts are run from it so errors show the same paths
+fn scripts() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("scripts")
+}
+
+/// Run a script with the nolang binary and the flags in `arguments`, with the vm when `vm` is set
+fn run(name: &str, arguments: &[&str], vm: bool) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_nolang"));
+    if vm {
+        command.arg("--vm");
+    }
+    command
+        .current_dir(scripts())
+        .args(arguments)
+        .arg(name)
+        .output()
+        .expect("can't run nolang")
+}
+
+/// Run a script with both backends, check that they agree and return what they printed
+fn both(name: &str) -> String {
+    both_with(name, &[])
+}
+
+/// Run a script with both backends and the flags in `arguments`, like `both`
+fn both_with(name: &str, arguments: &[&str]) -> String {
+    let (walker, vm) = (run(name, arguments, false), run(name, arguments, true));
+    let show = |output: &Output| {
+        String::from_utf8_lossy(&output.stdout).to_string() + &String::from_utf8_lossy(&output.stderr)
+    };
+
+    assert_eq!(
+        show(&walker),
+        show(&vm),
+        "the backends printed different output for {}",
+        name
+    );
+    assert_eq!(
+        walker.status.code(),
+        vm.status.code(),
+        "the backends exited differently for {}",
+        name
+    );
+    show(&walker)
+}
+
+/// Where the error a script stops with points at, as `line:column`
+fn error_at(name: &str) -> String {
+    let output = both(name);
+    let location = output
+        .lines()
+        .find_map(|line| line.trim_start().strip_prefix("--> "))
+        .unwrap_or_else(|| panic!("{} didn't stop with an error", name));
+    let mut parts = location.rsplitn(3, ':');
+    let column = parts.next().unwrap();
+    let line = parts.next().unwrap();
+    format!("{}:{}", line, column)
+}
+
+/// Every script in `tests/scripts/programs` prints what the `.out` file with its name has
+#[test]
+fn programs_print_their_expected_output() {
+    let mut paths: Vec<PathBuf> = fs::read_dir(scripts().join("programs"))
+        .expect("can't read the programs")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "no"))
+        .collect();
+    paths.sort();
+
+    for path in paths {
+        let name = format!("programs/{}", path.file_name().unwrap().to_string_lossy());
+        let expected = fs::read_to_string(path.with_extension("out"))
+            .unwrap_or_else(|_| panic!("{} has no .out file with its output", name));
+        assert_eq!(both(&name), expected, "{} printed something else", name);
+    }
+}
+
+#[test]
+fn module_functions_use_the_globals_of_their_module() {
+    assert_eq!(both("modules/shadowed_global.no"), "6\n");
+    assert_eq!(both("modules/module_global.no"), "6\n");
+}
+
+#[test]
+fn every_error_of_an_imported_module_is_reported() {
+    let output = both("modules/import_unparsable.no");
+    assert!(output.starts_with("error[ParseError]: Unexpected `)`"), "{}", output);
+    assert!(output.contains("- ParseError: expected one of: Comma"), "{}", output);
+
+    let output = both("modules/import_mistyped.no");
+    assert!(output.contains("`first`: expected `Int`, found `Str`"), "{}", output);
+    assert!(output.contains("`second`: expected `Int`, found `Str`"), "{}", output);
+}
+
+#[test]
+fn imported_modules_arent_type_checked_with_no_check() {
+    assert_eq!(both_with("modules/import_mistyped.no", &["--no-check"]), "one two\n");
+}
+
+#[test]
+fn lists_and_maps_that_contain_themselves_are_printed() {
+    let expected = "[1, [...]]\n{\"list\": [{...}, [1, [...]]], \"name\": \"m\", \"self\": {...}}\n[[2], [2]]\n";
+    assert_eq!(both("values/cyclic.no"), expected);
+}
+
+#[test]
+fn lists_and_maps_that_contain_themselves_are_compared() {
+    assert_eq!(both("values/cyclic_equality.no"), "true true\nfalse\ntrue true\ntrue\n");
+}
+
+#[test]
+fn errors_of_indexing_and_slicing_point_at_the_operation() {
+    assert_eq!(error_at("errors/index.no"), "2:9");
+    assert_eq!(error_at("errors/index_not_whole.no"), "2:9");
+    assert_eq!(error_at("errors/slice.no"), "2:9");
+    assert_eq!(error_at("errors/list.no"), "2:13");
+}
+
+#[test]
+fn errors_of_fields_and_map_entries_point_at_the_operation() {
+    assert_eq!(error_at("errors/access.no"), "2:9");
+    assert_eq!(error_at("errors/map.no"), "2:23");
+}
+
+#[test]
+fn errors_inside_of_string_templates_point_at_the_expression() {
+    assert_eq!(error_at("errors/template.no"), "2:27");
+}
+
+#[test]
+fn parse_errors_inside_of_string_templates_are_reported() {
+    let output = both("errors/template_block.no");
+    assert!(output.starts_with("error[ParseError]: Unexpected `*`"), "{}", output);
+    assert_eq!(error_at("errors/template_block.no"), "3:11");
+}
+
+#[test]
+fn powers_of_integers_can_be_integers() {
+    assert_eq!(both("types/int_power.no"), "2 0.5\n");
+}
+
+#[test]
+fn the_remainder_of_floats_has_the_sign_of_the_divisor() {
+    assert_eq!(
+        both("values/float_remainder.no"),
+        "2 -2 0.5 -0.5 0 0 NaN 2.5\n-4 -7.5\n"
+    );
+}
+
+#[test]
+fn ranges_too_big_for_a_list_are_errors() {
+    assert_eq!(
+        both("values/range.no"),
+        "[0, 1, 2] [-2, -1, 0] [] [0.5, 1.5, 2.5] 9999999\n"
+    );
+    assert_eq!(error_at("errors/range_too_big.no"), "2:10");
+    assert_eq!(error_at("errors/range_past_exact_floats.no"), "1:10");
+}
+
+#[test]
+fn math_keeps_integers_and_decimals_exact() {
+    assert_eq!(
+        both("values/math_exact.no"),
+        "9223372036854775807 Int 2.5 5 2.50\n7 Int -3 -2 -3\n-3 3 3 -3 12\n"
+    );
+    assert_eq!(error_at("errors/abs_overflow.no"), "1:9");
+    assert_eq!(error_at("errors/math_big_number.no"), "1:9");
+}
@@ -0,0 +1,142 @@
+//! Converts Rust types to nolang values and back with `#[derive(IntoPrimitive, FromPrimitive)]`
+
+use nolang::{Engine, ErrorKind, FromPrimitive, IntoPrimitive};
+use std::fmt::Debug;
+
+#[derive(IntoPrimitive, FromPrimitive, Debug, Clone, PartialEq)]
+struct Config {
+    name: String,
+    retries: i64,
+    ratio: Option<f64>,
+}
+
+#[derive(IntoPrimitive, FromPrimitive, Debug, Clone, PartialEq)]
+struct Pair(i64, String);
+
+#[derive(IntoPrimitive, FromPrimitive, Debug, Clone, PartialEq)]
+struct Meters(f64);
+
+#[derive(IntoPrimitive, FromPrimitive, Debug, Clone, PartialEq)]
+struct Nothing;
+
+#[derive(IntoPrimitive, FromPrimitive, Debug, Clone, PartialEq)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Segment(i64, i64),
+    Rectangle { width: i64, height: i64 },
+}
+
+#[derive(IntoPrimitive, FromPrimitive, Debug, Clone, PartialEq)]
+struct Labeled<T> {
+    label: String,
+    value: T,
+}
+
+/// Pass `value` to nolang as a global, check that the code sees it as `expected` and read it back
+fn round_trip<T>(value: T, expected: &str)
+where
+    T: IntoPrimitive + FromPrimitive + Debug + Clone + PartialEq,
+{
+    let mut engine = Engine::new();
+    engine.set_global("value", value.clone());
+    assert_eq!(engine.eval::<String>("str(value)").unwrap(), expected);
+    assert_eq!(engine.get_global::<T>("value").unwrap(), value);
+}
+
+#[test]
+fn structs_round_trip() {
+    let config = Config {
+        name: "nolang".to_string(),
+        retries: 3,
+        ratio: Some(0.5),
+    };
+    round_trip(config, r#"{"name": "nolang", "ratio": 0.5, "retries": 3}"#);
+    round_trip(Pair(1, "one".to_string()), r#"[1, "one"]"#);
+    round_trip(Meters(2.5), "2.5");
+    round_trip(Nothing, "none");
+}
+
+#[test]
+fn enum_variants_round_trip() {
+    round_trip(Shape::Empty, "Empty");
+    round_trip(Shape::Circle(1.5), r#"{"Circle": 1.5}"#);
+    round_trip(Shape::Segment(1, 2), r#"{"Segment": [1, 2]}"#);
+    round_trip(
+        Shape::Rectangle { width: 2, height: 3 },
+        r#"{"Rectangle": {"height": 3, "width": 2}}"#,
+    );
+}
+
+#[test]
+fn generic_structs_round_trip() {
+    let labeled = Labeled {
+        label: "origin".to_string(),
+        value: Pair(0, "zero".to_string()),
+    };
+    round_trip(labeled, r#"{"label": "origin", "value": [0, "zero"]}"#);
+    round_trip(
+        Labeled {
+            label: "none".to_string(),
+            value: None::<i64>,
+        },
+        r#"{"label": "none", "value": none}"#,
+    );
+}
+
+#[test]
+fn values_made_by_the_code_are_converted() {
+    let mut engine = Engine::new();
+
+    let config = engine.eval::<Config>(r#"{ name: "script", retries: 2 }"#).unwrap();
+    let expected = Config {
+        name: "script".to_string(),
+        retries: 2,
+        ratio: None,
+    };
+    assert_eq!(config, expected);
+    let shape = engine
+        .eval::<Shape>("{ Rectangle: { width: 1, height: 1 + 1 } }")
+        .unwrap();
+    assert_eq!(shape, Shape::Rectangle { width: 1, height: 2 });
+}
+
+#[test]
+fn missing_fields_say_which_field_it_was() {
+    let mut engine = Engine::new();
+
+    let error = engine.eval::<Config>(r#"{ name: "script" }"#).unwrap_err();
+    assert_eq!(
+        (error.kind, error.message.as_str()),
+        (ErrorKind::Type, "expected `Int`, found `None`")
+    );
+    let notes: Vec<&str> = error.notes.iter().map(|note| note.message.as_str()).collect();
+    assert_eq!(notes, ["in field `retries` of `Config`"]);
+}
+
+#[test]
+fn values_of_the_wrong_type_are_errors() {
+    let mut engine = Engine::new();
+    let mut message = |source: &str| engine.eval::<Shape>(source).unwrap_err().message;
+
+    assert_eq!(message("[1]"), "expected a variant of `Shape`, found `List`");
+    assert_eq!(message(r#""Square""#), "`Shape` has no variant named `Square`");
+    assert_eq!(
+        message("{ Segment: [1] }"),
+        "expected a list of 2 items for `Shape::Segment`, found [1]"
+    );
+    assert_eq!(
+        message("{ Rectangle: 1 }"),
+        "expected a map for `Shape::Rectangle`, found `Int`"
+    );
+
+    let error = engine.eval::<Config>(r#"{ name: 1, retries: 2 }"#).unwrap_err();
+    assert_eq!(error.message, "expected `Str`, found `Int`");
+    assert_eq!(error.notes[0].message, "in field `name` of `Config`");
+    let error = engine.eval::<Pair>(r#"[1, 2]"#).unwrap_err();
+    assert_eq!(error.notes[0].message, "in item 1 of `Pair`");
+    assert_eq!(
+        engine.eval::<Nothing>("1").unwrap_err().message,
+        "expected `none` for `Nothing`, found `Int`"
+    );
+}
@@ -0,0 +1,19 @@
+//! Renders errors with the `DiagnosticRenderer`
+
+use nolang::{DiagnosticRenderer, Engine};
+
+#[test]
+fn notes_without_a_span_have_no_colors_when_colors_are_off() {
+    let source = "half(\"a\")";
+    let mut engine = Engine::new();
+    engine.register_fn("half", |x: f64| x / 2.0);
+    let error = engine.eval::<f64>(source).unwrap_err();
+
+    let mut renderer = DiagnosticRenderer::default().with_color(false);
+    renderer.add_source("<eval>", source);
+    let rendered = renderer.render(&error);
+
+    assert!(rendered.contains("  = note: in argument 1 of `half`\n"), "{}", rendered);
+    assert!(!rendered.contains('\x1b'), "{:?}", rendered);
+    assert!(renderer.with_color(true).render(&error).contains('\x1b'));
+}
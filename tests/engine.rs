@@ -0,0 +1,176 @@
+//! Runs nolang code through the `Engine`, on threads that have the default native stack
+
+use nolang::{Engine, ErrorKind, IntoPrimitive, NolangError};
+use std::{rc::Rc, thread};
+
+const RECURSIVE: &str = "defn(n) depth = if n == 0 then 0 else 1 + depth(n - 1)";
+
+/// Run `test` on a new thread with the stack Rust gives threads by default
+fn on_thread(test: impl FnOnce() + Send + 'static) {
+    thread::spawn(test)
+        .join()
+        .expect("the engine overflowed the native stack");
+}
+
+#[test]
+fn recursion_up_to_the_default_limit_fits_in_a_thread() {
+    on_thread(|| {
+        let mut engine = Engine::new();
+        engine.eval::<()>(RECURSIVE).unwrap();
+
+        assert_eq!(engine.eval::<i64>("depth(999)").unwrap(), 999);
+        assert_eq!(engine.call_function::<i64>("depth", vec![998.into_pri()]).unwrap(), 998);
+
+        let error = engine.eval::<i64>("depth(1000)").unwrap_err();
+        assert_eq!(error.kind, ErrorKind::StackOverflow);
+    });
+}
+
+#[test]
+fn a_higher_limit_doesnt_need_a_bigger_thread() {
+    on_thread(|| {
+        let mut engine = Engine::with_max_depth(20_000);
+        engine.eval::<()>(RECURSIVE).unwrap();
+
+        assert_eq!(engine.eval::<i64>("depth(19999)").unwrap(), 19999);
+    });
+}
+
+#[test]
+fn dropping_the_engine_frees_its_functions_and_their_scopes() {
+    let probe = Rc::new(());
+    let held = probe.clone();
+    let mut engine = Engine::new();
+    engine.register_fn("probe", move || Rc::strong_count(&held) as i64);
+
+    // the scopes of the calls keep the probe, and the helpers stored in them keep the scopes
+    let source = "defn(n) outer = do
+        let keep = probe
+        defn(x) helper = x + keep()
+        if n == 0 then helper(0) else helper(outer(n - 1))
+    end
+    defn(n) last = do
+        let keep = probe
+        defn(x) helper = x + keep()
+        helper(n)
+    end
+    outer(3) + last(0)";
+    assert_eq!(engine.eval::<i64>(source).unwrap(), 10);
+
+    drop(engine);
+    assert_eq!(Rc::strong_count(&probe), 1);
+}
+
+#[test]
+fn engines_have_their_own_random_numbers() {
+    let (mut first, mut second) = (Engine::new(), Engine::new());
+    let random = |engine: &mut Engine| engine.eval::<f64>("math.random()").unwrap();
+
+    // seeding and taking numbers in one engine doesn't move the numbers of the other one
+    first.eval::<()>("math.seed(7)").unwrap();
+    let before = random(&mut first);
+    second.eval::<()>("math.seed(100)").unwrap();
+    random(&mut second);
+    let after = random(&mut first);
+
+    second.eval::<()>("math.seed(7)").unwrap();
+    assert_eq!(random(&mut second), before);
+    assert_eq!(random(&mut second), after);
+}
+
+#[test]
+fn engines_have_their_own_decimal_precision() {
+    let (mut first, mut second) = (Engine::new(), Engine::new());
+    let third = |engine: &mut Engine| engine.eval::<String>("str(2d / 3)").unwrap();
+
+    first.eval::<()>("decimal_precision(5)").unwrap();
+    first.eval::<()>("decimal_rounding(\"down\")").unwrap();
+    assert_eq!(third(&mut first), "0.66666");
+    assert_eq!(third(&mut second), "0.6666666666666666666666666667");
+}
+
+#[test]
+fn every_error_of_the_source_is_returned() {
+    let mut engine = Engine::new();
+
+    let error = engine.eval::<()>("writeln(1 +)\ndefn(a b) f = a\nlet z = )").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Parse);
+    let notes: Vec<&str> = error.notes.iter().map(|note| note.message.as_str()).collect();
+    assert_eq!(notes.len(), 2, "{:?}", notes);
+    assert!(notes.iter().all(|note| note.starts_with("ParseError: ")), "{:?}", notes);
+
+    let error = engine.eval::<()>("let a: Int = \"x\"\nlet b: Int = \"y\"").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(error.notes.len(), 1);
+    assert_eq!(error.notes[0].span.as_ref().unwrap().line, 2);
+}
+
+#[test]
+fn globals_set_by_the_host_are_read_back() {
+    let mut engine = Engine::new();
+    engine.set_global("name", "nolang");
+    engine.set_global("sizes", vec![1i64, 2, 3]);
+    engine.set_global("missing", None::<f64>);
+
+    assert_eq!(engine.get_global::<String>("name").unwrap(), "nolang");
+    assert_eq!(engine.get_global::<Vec<i64>>("sizes").unwrap(), vec![1, 2, 3]);
+    assert_eq!(engine.get_global::<Option<f64>>("missing").unwrap(), None);
+
+    // globals defined by the code are read the same way, and the host sees their changes
+    engine.eval::<()>("let total = sizes[0] + sizes[2]").unwrap();
+    assert_eq!(engine.get_global::<i64>("total").unwrap(), 4);
+    engine.set_global("total", 10i64);
+    assert_eq!(engine.eval::<i64>("total + 1").unwrap(), 11);
+
+    let error = engine.get_global::<i64>("name").unwrap_err();
+    assert_eq!((error.kind, error.message.as_str()), (ErrorKind::Type, "expected `Int`, found `Str`"));
+    assert_eq!(engine.get_global::<i64>("undefined").unwrap_err().kind, ErrorKind::Reference);
+}
+
+#[test]
+fn functions_of_the_host_raise_the_errors_they_return() {
+    let mut engine = Engine::new();
+    engine.register_fn("root", |x: f64| match x < 0.0 {
+        true => Err(NolangError::new(ErrorKind::Runtime, format!("no real root of {}", x))),
+        false => Ok(x.sqrt()),
+    });
+
+    assert_eq!(engine.eval::<f64>("root(9)").unwrap(), 3.0);
+    let error = engine.eval::<f64>("root(-4)").unwrap_err();
+    assert_eq!((error.kind, error.message.as_str()), (ErrorKind::Runtime, "no real root of -4"));
+    assert!(error.span.is_some());
+}
+
+#[test]
+fn functions_of_the_host_check_how_many_arguments_they_get() {
+    let mut engine = Engine::new();
+    engine.register_fn("add", |a: i64, b: i64| a + b);
+
+    for (source, given) in [("add(1)", 1), ("add(1, 2, 3)", 3)] {
+        let error = engine.eval::<i64>(source).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::Call);
+        assert_eq!(error.message, format!("`add` takes 2 arguments but {} were given", given));
+    }
+    let error = engine.call_function::<i64>("add", vec![1.into_pri()]).unwrap_err();
+    assert_eq!(error.message, "`add` takes 2 arguments but 1 were given");
+}
+
+#[test]
+fn arguments_of_the_wrong_type_say_which_argument_it_was() {
+    let mut engine = Engine::new();
+    engine.register_fn("add", |a: i64, b: i64| a + b);
+
+    let error = engine.eval::<i64>("add(1, \"b\")").unwrap_err();
+    assert_eq!((error.kind, error.message.as_str()), (ErrorKind::Type, "expected `Int`, found `Str`"));
+    let notes: Vec<&str> = error.notes.iter().map(|note| note.message.as_str()).collect();
+    assert_eq!(notes, ["in argument 2 of `add`"]);
+}
+
+#[test]
+fn calling_an_undefined_function_is_an_error() {
+    let mut engine = Engine::new();
+
+    let error = engine.call_function::<()>("missing", vec![]).unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Reference);
+    assert!(error.message.contains("missing"), "{}", error.message);
+}
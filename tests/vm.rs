@@ -0,0 +1,37 @@
+//! Runs compiled nolang code in vms that live side by side
+
+use nolang::compiler::compile;
+use nolang::frontend::parse;
+use nolang::vm::Vm;
+use nolang::Primitive;
+
+/// Compile and run `source` in `vm`, returning the value of its last statement
+fn run(vm: &mut Vm, source: &str) -> Primitive {
+    let program = parse(source.to_string(), "<test>").unwrap();
+    vm.run(compile(program).unwrap()).unwrap()
+}
+
+#[test]
+fn vms_have_their_own_random_numbers() {
+    let (mut first, mut second) = (Vm::default(), Vm::default());
+
+    run(&mut first, "math.seed(7)");
+    let before = run(&mut first, "math.random()").to_string();
+    run(&mut second, "math.seed(100)");
+    run(&mut second, "math.random()");
+    let after = run(&mut first, "math.random()").to_string();
+
+    run(&mut second, "math.seed(7)");
+    assert_eq!(run(&mut second, "math.random()").to_string(), before);
+    assert_eq!(run(&mut second, "math.random()").to_string(), after);
+}
+
+#[test]
+fn vms_have_their_own_decimal_precision() {
+    let (mut first, mut second) = (Vm::default(), Vm::default());
+
+    run(&mut first, "decimal_precision(5)");
+    run(&mut first, "decimal_rounding(\"down\")");
+    assert_eq!(run(&mut first, "2d / 3").to_string(), "0.66666");
+    assert_eq!(run(&mut second, "2d / 3").to_string(), "0.6666666666666666666666666667");
+}